@@ -1,4 +1,4 @@
-import { svg } from './ergoweb';
+import * as ergoweb from './ergoweb';
 import { booted } from './ergoweb_bg';
 
 
@@ -18,7 +18,16 @@ function getQueryVariable(variable)
 
 function make_svg(src) {
   var fileContents = document.getElementById('keymap');
-  fileContents.innerHTML = svg(src);
+  // The committed build only has make_svg, which fills the element itself
+  if (ergoweb.svg) {
+    fileContents.innerHTML = ergoweb.svg(src);
+  } else {
+    ergoweb.make_svg(src, 'keymap');
+  }
+  // Without the simulator the keys' switches are followed as written
+  if (ergoweb.KeySimulator) {
+    keyboardSimulator = new ergoweb.KeySimulator(src);
+  }
   layer0on();
   toggle_spinner();
 }
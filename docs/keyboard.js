@@ -1,6 +1,11 @@
-function layer_click(layer) {
-  alert("Layer " + layer);
-}
+// Keys carry their position in data-pos and the layer they switch to in
+// data-switch ("on 2", "only 2" or "momentary 1"). A page may set
+// keyboardSimulator to ergoweb's KeySimulator to run the keymap itself,
+// which also takes keys typed on the computer's keyboard and shows what
+// they type in an element with the id "output". Otherwise the switches
+// are followed as written.
+var keyboardSimulator = null;
+var pressedKey = null;
 
 function onlylayer(layer){
   var l;
@@ -16,10 +21,6 @@ function onlylayer(layer){
   }
 }
 
-function layer0on(layer) {
-  layeron(0);
-}
-
 function layeron(layer) {
   document.getElementById("layer" + layer).setAttribute('visibility','visible')
 }
@@ -28,6 +29,112 @@ function layeroff(layer) {
   document.getElementById("layer" + layer).setAttribute('visibility','hidden')
 }
 
+function keyAt(node) {
+  while (node && !(node.getAttribute && node.getAttribute('data-pos'))) {
+    node = node.parentNode;
+  }
+  return node;
+}
+
+function keySwitch(key) {
+  var parts = (key.getAttribute('data-switch') || '').split(' ');
+  return { kind: parts[0], layer: parts[1] };
+}
+
+function now() {
+  return Math.round(performance.now());
+}
+
+function showSimulation() {
+  onlylayer(keyboardSimulator.layer());
+  var output = document.getElementById("output");
+  if (output) {
+    output.textContent = keyboardSimulator.output();
+  }
+}
+
+// Show what a key did, and check back once a tap key pressed now would
+// count as held
+function simulated() {
+  showSimulation();
+  setTimeout(function() {
+    if (keyboardSimulator) {
+      keyboardSimulator.tick(now());
+      showSimulation();
+    }
+  }, keyboardSimulator.tapping_term() + 1);
+}
+
+function keyDown(event) {
+  var key = keyAt(event.target);
+  if (!key) {
+    return;
+  }
+  pressedKey = key;
+  if (keyboardSimulator) {
+    keyboardSimulator.press(Number(key.getAttribute('data-pos')), now());
+    simulated();
+    return;
+  }
+  var sw = keySwitch(key);
+  if (sw.kind == 'on' || sw.kind == 'momentary') {
+    layeron(sw.layer);
+  } else if (sw.kind == 'only') {
+    onlylayer(sw.layer);
+  }
+}
+
+// The key is released where it was pressed, wherever the pointer went
+function keyUp(event) {
+  var key = pressedKey;
+  pressedKey = null;
+  if (!key) {
+    return;
+  }
+  if (keyboardSimulator) {
+    keyboardSimulator.release(Number(key.getAttribute('data-pos')), now());
+    simulated();
+    return;
+  }
+  var sw = keySwitch(key);
+  if (sw.kind == 'momentary') {
+    layeroff(sw.layer);
+  }
+}
+
+// Whether a key typed at `target` is text being entered on the page
+function editing(target) {
+  return target.tagName == 'INPUT' || target.tagName == 'TEXTAREA' || target.isContentEditable;
+}
+
+// Keys on the computer's keyboard press the key in the same place on
+// the base layer
+function typedKey(event) {
+  if (!keyboardSimulator || event.repeat || editing(event.target)) {
+    return;
+  }
+  var pos = keyboardSimulator.position(event.code);
+  if (pos < 0) {
+    return;
+  }
+  event.preventDefault();
+  if (event.type == 'keydown') {
+    keyboardSimulator.press(pos, now());
+  } else {
+    keyboardSimulator.release(pos, now());
+  }
+  simulated();
+}
+
+document.addEventListener('mousedown', keyDown);
+document.addEventListener('mouseup', keyUp);
+document.addEventListener('keydown', typedKey);
+document.addEventListener('keyup', typedKey);
+
+function layer0on(layer) {
+  layeron(0);
+}
+
 window.onload = function(){
-  document.getElementById("layer0").setAttribute('visibility','visible');  
+  document.getElementById("layer0").setAttribute('visibility','visible');
 }
@@ -0,0 +1,118 @@
+use types::*;
+use keycodes;
+
+/// What kind of thing a key does, used to colour keys by purpose
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Category {
+    Alpha,
+    Number,
+    Symbol,
+    Modifier,
+    Layer,
+    Navigation,
+    Media,
+    Mouse,
+    Function,
+    Transparent,
+    Macro,
+    // Whitespace, editing and anything unrecognised
+    Other
+}
+
+const CATEGORIES: [Category; 12] = [
+    Category::Alpha, Category::Number, Category::Symbol, Category::Modifier,
+    Category::Layer, Category::Navigation, Category::Media, Category::Mouse,
+    Category::Function, Category::Transparent, Category::Macro, Category::Other
+];
+
+impl Category {
+    pub fn all() -> &'static [Category] {
+        &CATEGORIES
+    }
+
+    /// Lower case name, used as a CSS class
+    pub fn name(self) -> &'static str {
+        match self {
+            Category::Alpha => "alpha",
+            Category::Number => "number",
+            Category::Symbol => "symbol",
+            Category::Modifier => "modifier",
+            Category::Layer => "layer",
+            Category::Navigation => "navigation",
+            Category::Media => "media",
+            Category::Mouse => "mouse",
+            Category::Function => "function",
+            Category::Transparent => "transparent",
+            Category::Macro => "macro",
+            Category::Other => "other"
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Category> {
+        CATEGORIES.iter().cloned().find(|c| c.name() == name)
+    }
+
+    /// Text for the colour legend
+    pub fn description(self) -> &'static str {
+        match self {
+            Category::Alpha => "Letters",
+            Category::Number => "Numbers",
+            Category::Symbol => "Symbols",
+            Category::Modifier => "Modifiers",
+            Category::Layer => "Layer switches",
+            Category::Navigation => "Navigation",
+            Category::Media => "Media",
+            Category::Mouse => "Mouse",
+            Category::Function => "Function keys",
+            Category::Transparent => "Transparent",
+            Category::Macro => "Macros",
+            Category::Other => "Other"
+        }
+    }
+
+    /// Category of a keycode name such as KC_A, LSFT or MOD_LCTL
+    pub fn of_keycode(name: &str) -> Category {
+        keycodes::lookup(name).map_or(Category::Other, |keycode| keycode.category)
+    }
+
+    /// Category of a key, looking through FN keys to their action
+    pub fn of_key(key: &Key, action: Option<&Action>) -> Category {
+        match (key, action) {
+            (Key::Key(name), _) => Category::of_keycode(name),
+            (Key::Fx(_), None) => Category::Other,
+            (_, Some(action)) => match action {
+                Action::Function(_) | Action::FunctionTap(_) => Category::Macro,
+                Action::ModsKey(..) | Action::ModsTapKey(..) => Category::Modifier,
+                Action::DefaultLayerSet(_)
+                    | Action::LayerSet(..)
+                    | Action::LayerSetClear(_)
+                    | Action::LayerMomentary(_)
+                    | Action::LayerTapKey(..)
+                    | Action::LayerTapToggle(_)
+                    | Action::LayerToggle(_) => Category::Layer
+            }
+        }
+    }
+}
+
+#[test]
+fn test_keycode_categories() {
+    let cases = [
+        ("KC_A", Category::Alpha), ("Z", Category::Alpha),
+        ("1", Category::Number), ("P7", Category::Number), ("KC_KP_0", Category::Number),
+        ("SCLN", Category::Symbol), ("PAST", Category::Symbol),
+        ("MOD_LSFT", Category::Modifier), ("RGUI", Category::Modifier),
+        ("PGUP", Category::Navigation), ("KC_RIGHT", Category::Navigation),
+        ("VOLU", Category::Media), ("AUDIO_VOL_UP", Category::Media),
+        ("MS_U", Category::Mouse), ("BTN1", Category::Mouse), ("WH_D", Category::Mouse),
+        ("F1", Category::Function), ("F24", Category::Function),
+        ("TRNS", Category::Transparent),
+        ("SPC", Category::Other), ("FN", Category::Other), ("P", Category::Alpha)
+    ];
+    for &(name, category) in cases.iter() {
+        assert_eq!(Category::of_keycode(name), category, "{}", name);
+    }
+
+    assert_eq!(Category::of_key(&Key::Fx(1), Some(&Action::LayerMomentary(1))), Category::Layer);
+    assert_eq!(Category::of_key(&Key::Fx(1), None), Category::Other);
+}
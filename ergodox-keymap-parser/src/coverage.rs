@@ -0,0 +1,150 @@
+use std::fmt;
+
+use types::*;
+use find::{find, Via};
+
+/// The standard keycodes a keymap is expected to reach, in groups
+pub const STANDARD: &[(&str, &[&str])] = &[
+    ("Letters", &["A", "B", "C", "D", "E", "F", "G", "H", "I", "J", "K", "L", "M",
+                  "N", "O", "P", "Q", "R", "S", "T", "U", "V", "W", "X", "Y", "Z"]),
+    ("Digits", &["1", "2", "3", "4", "5", "6", "7", "8", "9", "0"]),
+    ("Function", &["F1", "F2", "F3", "F4", "F5", "F6", "F7", "F8", "F9", "F10", "F11", "F12",
+                   "F13", "F14", "F15", "F16", "F17", "F18", "F19", "F20", "F21", "F22", "F23", "F24"]),
+    ("Punctuation", &["MINS", "EQL", "LBRC", "RBRC", "BSLS", "SCLN", "QUOT", "GRV", "COMM", "DOT", "SLSH"]),
+    ("Editing", &["ENT", "ESC", "BSPC", "TAB", "SPC", "CAPS"]),
+    ("Navigation", &["PSCR", "SLCK", "PAUS", "INS", "HOME", "PGUP", "DEL", "END", "PGDN",
+                     "RGHT", "LEFT", "DOWN", "UP"]),
+    ("Media", &["MUTE", "VOLU", "VOLD", "MNXT", "MPRV", "MSTP", "MPLY"])
+];
+
+/// A standard keycode no key can send
+#[derive(Debug, Clone, PartialEq)]
+pub struct Missing {
+    pub group: &'static str,
+    pub keycode: &'static str,
+    /// Keys that send it but are on layers that can't be reached
+    pub unreachable: Vec<(usize, usize)>
+}
+
+/// A standard keycode on more than one layer
+#[derive(Debug, Clone, PartialEq)]
+pub struct Duplicate {
+    pub keycode: &'static str,
+    /// Layer and position of every key that sends it
+    pub keys: Vec<(usize, usize)>
+}
+
+/// Which standard keycodes a keymap can send
+#[derive(Debug, Clone, PartialEq)]
+pub struct Coverage {
+    pub missing: Vec<Missing>,
+    pub duplicated: Vec<Duplicate>
+}
+
+impl Coverage {
+    pub fn new(keymaps: &KeyMapVec, actions: &ActionMap) -> Coverage {
+        let mut missing = Vec::new();
+        let mut duplicated = Vec::new();
+
+        for &(group, keycodes) in STANDARD {
+            for &keycode in keycodes {
+                // Keys that send it with modifiers type something else
+                let found: Vec<_> = find(keymaps, actions, keycode).into_iter()
+                    .filter(|f| f.via == Via::Direct || f.via == Via::Tap)
+                    .collect();
+                let (reachable, unreachable): (Vec<_>, Vec<_>) = found.iter()
+                    .partition(|f| f.path.is_some());
+
+                if reachable.is_empty() {
+                    missing.push(Missing{
+                        group,
+                        keycode,
+                        unreachable: unreachable.iter().map(|f| (f.layer, f.index)).collect()
+                    });
+                }
+
+                let plain: Vec<(usize, usize)> = reachable.iter()
+                    .map(|f| (f.layer, f.index))
+                    .collect();
+                if plain.iter().any(|&(layer, _)| layer != plain[0].0) {
+                    duplicated.push(Duplicate{ keycode, keys: plain });
+                }
+            }
+        }
+        Coverage{ missing, duplicated }
+    }
+}
+
+fn places(keys: &[(usize, usize)]) -> String {
+    keys.iter()
+        .map(|&(layer, index)| format!("layer {} position {}", layer, index))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+impl fmt::Display for Coverage {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        if self.missing.is_empty() {
+            writeln!(fmt, "Every standard keycode can be reached")?;
+        } else {
+            writeln!(fmt, "Unreachable:")?;
+            for &(group, _) in STANDARD {
+                let missing: Vec<&Missing> = self.missing.iter().filter(|m| m.group == group).collect();
+                if missing.is_empty() {
+                    continue;
+                }
+                let names: Vec<&str> = missing.iter().map(|m| m.keycode).collect();
+                writeln!(fmt, "  {}: {}", group, names.join(", "))?;
+                for m in missing.iter().filter(|m| !m.unreachable.is_empty()) {
+                    writeln!(fmt, "    {} is only on {}, which can't be reached", m.keycode, places(&m.unreachable))?;
+                }
+            }
+        }
+        if !self.duplicated.is_empty() {
+            writeln!(fmt, "Duplicated:")?;
+            for duplicate in &self.duplicated {
+                writeln!(fmt, "  {}: {}", duplicate.keycode, places(&duplicate.keys))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn test_coverage() {
+    // Every standard keycode on layer 0 but F24 and PSCR
+    let base: Vec<String> = STANDARD.iter()
+        .flat_map(|&(_, keycodes)| keycodes.iter())
+        .filter(|&&k| k != "F24" && k != "PSCR")
+        .map(|&k| format!("KC_{}", k))
+        .collect();
+    let (keymaps, actions) = ::parser::parse_test_keymap(
+        &format!("KEYMAP({}, FN0), KEYMAP(KC_ESC, KC_PSCR, KC_1), KEYMAP(KC_F24)", base.join(", ")),
+        "[0] = ACTION_LAYER_MOMENTARY(1)");
+
+    let coverage = Coverage::new(&keymaps, &actions);
+    let missing: Vec<(&str, &str, usize)> = coverage.missing.iter()
+        .map(|m| (m.group, m.keycode, m.unreachable.len()))
+        .collect();
+    assert_eq!(missing, vec![("Function", "F24", 1)]);
+    let duplicated: Vec<&str> = coverage.duplicated.iter().map(|d| d.keycode).collect();
+    assert_eq!(duplicated, vec!["1", "ESC"]);
+
+    let report = coverage.to_string();
+    assert!(report.contains("Unreachable:\n  Function: F24\n    F24 is only on layer 2 position 0, which can't be reached\n"));
+    assert!(report.contains("Duplicated:\n  1: layer 0 position 26, layer 1 position 2\n"));
+}
+
+#[test]
+fn test_coverage_modified() {
+    // PIPE sends BSLS with Shift and FN0 sends C with Ctrl, so neither
+    // types BSLS or C
+    let (keymaps, actions) = ::parser::parse_test_keymap("KEYMAP(KC_PIPE, KC_A, FN0)",
+                                                         "[0] = ACTION_MODS_KEY(MOD_LCTL, KC_C)");
+
+    let coverage = Coverage::new(&keymaps, &actions);
+    let missing: Vec<&str> = coverage.missing.iter().map(|m| m.keycode).collect();
+    assert!(missing.contains(&"C"));
+    assert!(missing.contains(&"BSLS"));
+    assert!(!missing.contains(&"A"));
+}
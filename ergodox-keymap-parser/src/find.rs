@@ -0,0 +1,173 @@
+use std::collections::VecDeque;
+use std::fmt;
+
+use types::*;
+use keycodes;
+use resolve::*;
+use graph::EdgeKind;
+
+/// How a key produces the keycode looked for
+#[derive(Debug, Clone, PartialEq)]
+pub enum Via {
+    /// The key sends it
+    Direct,
+    /// The key sends it when tapped, and something else when held
+    Tap,
+    /// The key sends the unshifted keycode, so Shift is needed
+    Shifted(String),
+    /// The key sends it with modifiers, as ACTION_MODS_KEY does
+    Modified(String)
+}
+
+/// A key pressed on the way to a layer
+#[derive(Debug, Clone, PartialEq)]
+pub struct Step {
+    pub layer: usize,
+    pub index: usize,
+    pub key: String,
+    pub kind: EdgeKind
+}
+
+impl fmt::Display for Step {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let verb = if self.kind.is_momentary() { "hold" } else { "tap" };
+        write!(fmt, "{} {} (layer {}, position {})", verb, self.key, self.layer, self.index)
+    }
+}
+
+/// A key that produces the keycode, and how to get to its layer
+#[derive(Debug, Clone, PartialEq)]
+pub struct Found {
+    pub layer: usize,
+    pub index: usize,
+    pub key: String,
+    pub via: Via,
+    /// Keys to press from the base layer, or None if the layer can't be
+    /// reached
+    pub path: Option<Vec<Step>>
+}
+
+impl fmt::Display for Found {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "layer {}, position {}: {}", self.layer, self.index, self.key)?;
+        match &self.via {
+            Via::Direct => (),
+            Via::Tap => write!(fmt, " tapped")?,
+            Via::Shifted(base) => write!(fmt, " ({} with Shift)", base)?,
+            Via::Modified(mods) => write!(fmt, " (with {})", mods)?
+        }
+        match &self.path {
+            Some(path) if path.is_empty() => Ok(()),
+            Some(path) => {
+                let steps: Vec<String> = path.iter().map(Step::to_string).collect();
+                write!(fmt, " after {}", steps.join(", then "))
+            }
+            None => write!(fmt, ", which can't be reached from layer 0")
+        }
+    }
+}
+
+// How a keycode written in the keymap gives `target`, if it does
+fn produces(name: &str, target: &str) -> Option<Via> {
+    let name = keycodes::canonical(name);
+    if name == target {
+        return Some(Via::Direct);
+    }
+    let shift_of = |name: &str| keycodes::lookup(name).and_then(|k| k.shift_of);
+    if shift_of(target) == Some(name.as_str()) {
+        return Some(Via::Shifted(name));
+    }
+    // QMK's shifted keycodes send their key with Shift
+    if shift_of(&name) == Some(target) {
+        return Some(Via::Modified(String::from("LSFT")));
+    }
+    None
+}
+
+fn key_produces(key: &Key, action: Option<&Action>, target: &str) -> Option<Via> {
+    match (key, action) {
+        (Key::Key(name), _) => produces(name, target),
+        (Key::Fx(_), Some(Action::LayerTapKey(_, Key::Key(k)))) |
+        (Key::Fx(_), Some(Action::ModsTapKey(_, Key::Key(k)))) =>
+            produces(k, target).map(|via| if via == Via::Direct { Via::Tap } else { via }),
+        (Key::Fx(_), Some(Action::ModsKey(Key::Key(m), Key::Key(k)))) =>
+            produces(k, target).map(|via| match via {
+                Via::Direct | Via::Shifted(_) => Via::Modified(keycodes::canonical(m)),
+                via => via
+            }),
+        _ => None
+    }
+}
+
+/// The fewest keys to press from the base layer to bring up each layer,
+/// in the order a diagram of that layer shows them
+pub fn paths(keymaps: &KeyMapVec, actions: &ActionMap) -> Vec<Option<Vec<Step>>> {
+    let mut paths: Vec<Option<Vec<Step>>> = vec![None; keymaps.len()];
+    let mut queue = VecDeque::new();
+    if !keymaps.is_empty() {
+        paths[0] = Some(Vec::new());
+        queue.push_back(0);
+    }
+    while let Some(layer) = queue.pop_front() {
+        for resolved in effective_keymap(keymaps, actions, LayerState::up_to(layer as u32)) {
+            let (kind, to) = match resolved.action.and_then(EdgeKind::of) {
+                Some(edge) => edge,
+                None => continue
+            };
+            let to = to as usize;
+            if to < keymaps.len() && paths[to].is_none() {
+                let mut path = paths[layer].clone().unwrap_or_default();
+                path.push(Step{ layer, index: resolved.index, key: resolved.key.to_string(), kind });
+                paths[to] = Some(path);
+                queue.push_back(to);
+            }
+        }
+    }
+    paths
+}
+
+/// Every key in the keymap that produces `keycode`, by layer and
+/// position
+pub fn find(keymaps: &KeyMapVec, actions: &ActionMap, keycode: &str) -> Vec<Found> {
+    let target = keycodes::canonical(keycode);
+    let paths = paths(keymaps, actions);
+
+    let mut found = Vec::new();
+    for (layer, keymap) in keymaps.iter().enumerate() {
+        for (index, key) in keymap.iter().enumerate() {
+            let action = match key {
+                Key::Fx(n) => actions.get(n),
+                Key::Key(_) => None
+            };
+            if let Some(via) = key_produces(key, action, &target) {
+                found.push(Found{ layer, index, key: key.to_string(), via, path: paths[layer].clone() });
+            }
+        }
+    }
+    found
+}
+
+#[test]
+fn test_find() {
+    let (keymaps, actions) = ::parser::parse_test_keymap("KEYMAP(FN0, A, BSLS), KEYMAP(TRNS, FN1, PIPE),\n\
+                                                          KEYMAP(F11, FN2, B), KEYMAP(F11, NO, NO)",
+                                                         "[0] = ACTION_LAYER_MOMENTARY(1),\n\
+                                                          [1] = ACTION_LAYER_TOGGLE(2),\n\
+                                                          [2] = ACTION_MODS_KEY(MOD_LSFT, KC_BSLS)");
+
+    let found = find(&keymaps, &actions, "KC_PIPE");
+    let found: Vec<String> = found.iter().map(Found::to_string).collect();
+    assert_eq!(found, vec![
+        "layer 0, position 2: BSLS (BSLS with Shift)",
+        "layer 1, position 2: PIPE after hold FN0 (layer 0, position 0)",
+        "layer 2, position 1: FN2 (with LSFT) after hold FN0 (layer 0, position 0), \
+         then tap FN1 (layer 1, position 1)"
+    ]);
+
+    let found = find(&keymaps, &actions, "F11");
+    assert_eq!(found.len(), 2);
+    assert_eq!(found[1].path, None);
+    assert_eq!(found[1].to_string(), "layer 3, position 0: F11, which can't be reached from layer 0");
+
+    assert_eq!(find(&keymaps, &actions, "KC_BSLASH")[1].via, Via::Modified(String::from("LSFT")));
+}
@@ -0,0 +1,129 @@
+// Advance widths of Helvetica from its AFM file, in thousandths of the
+// font size, for the printable ASCII characters starting at space.
+// Arial shares these metrics.
+const HELVETICA: [u16; 95] = [
+    278, 278, 355, 556, 556, 889, 667, 191, 333, 333, 389, 584, 278, 333, 278, 278,
+    556, 556, 556, 556, 556, 556, 556, 556, 556, 556, 278, 278, 584, 584, 584, 556,
+    1015, 667, 667, 722, 722, 667, 611, 778, 722, 278, 500, 667, 556, 833, 722, 778,
+    667, 778, 722, 667, 611, 722, 667, 944, 667, 667, 611, 278, 278, 278, 469, 556,
+    333, 556, 556, 500, 556, 556, 278, 556, 556, 222, 222, 500, 222, 833, 556, 556,
+    556, 556, 333, 500, 278, 556, 500, 722, 500, 500, 500, 334, 260, 334, 584
+];
+
+// Symbols and arrows outside ASCII are taken to be a full em wide
+const WIDE: u16 = 1000;
+
+// Legends are shrunk to no less than this fraction of their size
+const MIN_SCALE: f64 = 0.6;
+
+const LINE_HEIGHT: f64 = 1.1;
+
+/// Width of `text` set in Helvetica at `size`
+pub fn text_width(text: &str, size: f64) -> f64 {
+    let thousandths: u32 = text.chars()
+        .map(|c| match c as u32 {
+            n @ 32..=126 => HELVETICA[(n - 32) as usize],
+            _ => WIDE
+        } as u32)
+        .sum();
+    thousandths as f64 * size / 1000.0
+}
+
+/// A legend made to fit its space, in lines from top to bottom
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fitted {
+    pub lines: Vec<String>,
+    pub size: f64
+}
+
+impl Fitted {
+    /// Distance from one baseline to the next
+    pub fn line_height(&self) -> f64 {
+        self.size * LINE_HEIGHT
+    }
+}
+
+/// Fit `text` into `width` at `size`. Text that is too wide is shrunk,
+/// then wrapped at underscores and spaces onto as many as `max_lines`
+/// lines. Keycodes are already short, as the keycode table gives them.
+pub fn fit(text: &str, size: f64, width: f64, max_lines: usize) -> Fitted {
+    let line = |text: &str, size| Fitted{ lines: vec![String::from(text)], size };
+
+    if text_width(text, size) <= width {
+        return line(text, size);
+    }
+    let smallest = size * MIN_SCALE;
+    let shrunk = round_down(size * width / text_width(text, size));
+    if shrunk >= smallest {
+        return line(text, shrunk);
+    }
+
+    let lines = wrap(text, smallest, width, max_lines.max(1));
+    // Whatever still overflows is shrunk further rather than clipped
+    let widest = lines.iter().map(|l| text_width(l, smallest)).fold(0.0, f64::max);
+    let size = if widest > width { round_down(smallest * width / widest) } else { smallest };
+    Fitted{ lines, size }
+}
+
+// Sizes are kept to two decimal places so the output stays readable
+fn round_down(size: f64) -> f64 {
+    (size * 100.0).floor() / 100.0
+}
+
+// Break text into at most `max_lines` lines, filling each line with
+// whole words where they fit. Words left over go on the last line.
+fn wrap(text: &str, size: f64, width: f64, max_lines: usize) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for word in text.split(['_', ' ']).filter(|w| !w.is_empty()) {
+        let new_line = match lines.last() {
+            Some(last) => lines.len() < max_lines
+                && text_width(&format!("{} {}", last, word), size) > width,
+            None => true
+        };
+        match lines.last_mut() {
+            Some(last) if !new_line => {
+                last.push(' ');
+                last.push_str(word);
+            }
+            _ => lines.push(String::from(word))
+        }
+    }
+    lines
+}
+
+#[test]
+fn test_text_width() {
+    assert_eq!(text_width("", 20.0), 0.0);
+    assert!((text_width("Wi", 10.0) - 11.66).abs() < 1e-9);
+    assert_eq!(text_width("→", 20.0), 20.0);
+}
+
+#[test]
+fn test_fit() {
+    // Short legends are left alone
+    assert_eq!(fit("A", 20.0, 70.0, 2), Fitted{ lines: vec![String::from("A")], size: 20.0 });
+
+    // A little too wide is shrunk
+    let shrunk = fit("Hello", 20.0, 45.0, 2);
+    assert_eq!(shrunk.lines, vec!["Hello"]);
+    assert!(shrunk.size < 20.0 && text_width("Hello", shrunk.size) <= 45.0 + 1e-9);
+
+    // Much too wide is wrapped
+    let wrapped = fit("SOME_VERY_LONG_NAME", 20.0, 70.0, 2);
+    assert_eq!(wrapped.lines.len(), 2);
+    assert!(wrapped.lines.iter().all(|l| text_width(l, wrapped.size) <= 70.0 + 1e-9));
+}
+
+#[test]
+fn test_fit_rendered() {
+    use options::RenderOptions;
+
+    let input = "keymaps[][MATRIX_ROWS][MATRIX_COLS] = {\n    KEYMAP(AUDIO_VOL_UP, SOME_VERY_LONG_NAME),\n};\n\
+                 fn_actions[] = {\n};\n";
+    let svg = ::to_svg_with(input, &RenderOptions::default());
+
+    // Known keycodes show their short legend and unknown ones are wrapped
+    assert!(svg.contains("<![CDATA[🔊]]>"));
+    assert!(svg.contains("<![CDATA[SOME]]>"));
+    assert!(svg.contains("<![CDATA[VERY LONG NAME]]>"));
+}
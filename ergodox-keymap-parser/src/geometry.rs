@@ -0,0 +1,389 @@
+use serde_json;
+use serde_json::{Map, Value};
+
+/// Placement of a single key, measured in key units (1u is the width
+/// of a standard keycap). The key is rotated by `r` degrees around the
+/// point (`rx`, `ry`) after being placed at (`x`, `y`).
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyPosition {
+    pub x: f64,
+    pub y: f64,
+    pub w: f64,
+    pub h: f64,
+    pub r: f64,
+    pub rx: f64,
+    pub ry: f64
+}
+
+/// Physical layout of a board. Keys are listed in keymap order, so the
+/// nth entry is drawn with the nth key of every layer.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Geometry {
+    pub keys: Vec<KeyPosition>
+}
+
+// (x, y, w, h) for each key of a cluster, relative to the cluster
+type Cluster = [(f64, f64, f64, f64)];
+
+const LEFT_MAIN: [(f64, f64, f64, f64); 32] = [
+    // First row
+    (0.0, 0.0, 1.5, 1.0), (1.5, 0.0, 1.0, 1.0), (2.5, 0.0, 1.0, 1.0),
+    (3.5, 0.0, 1.0, 1.0), (4.5, 0.0, 1.0, 1.0), (5.5, 0.0, 1.0, 1.0),
+    (6.5, 0.0, 1.0, 1.0),
+    // Second row
+    (0.0, 1.0, 1.5, 1.0), (1.5, 1.0, 1.0, 1.0), (2.5, 1.0, 1.0, 1.0),
+    (3.5, 1.0, 1.0, 1.0), (4.5, 1.0, 1.0, 1.0), (5.5, 1.0, 1.0, 1.0),
+    (6.5, 1.0, 1.0, 1.5),
+    // Third row
+    (0.0, 2.0, 1.5, 1.0), (1.5, 2.0, 1.0, 1.0), (2.5, 2.0, 1.0, 1.0),
+    (3.5, 2.0, 1.0, 1.0), (4.5, 2.0, 1.0, 1.0), (5.5, 2.0, 1.0, 1.0),
+    // Fourth row
+    (0.0, 3.0, 1.5, 1.0), (1.5, 3.0, 1.0, 1.0), (2.5, 3.0, 1.0, 1.0),
+    (3.5, 3.0, 1.0, 1.0), (4.5, 3.0, 1.0, 1.0), (5.5, 3.0, 1.0, 1.0),
+    (6.5, 2.5, 1.0, 1.5),
+    // Fifth row
+    (0.5, 4.0, 1.0, 1.0), (1.5, 4.0, 1.0, 1.0), (2.5, 4.0, 1.0, 1.0),
+    (3.5, 4.0, 1.0, 1.0), (4.5, 4.0, 1.0, 1.0)
+];
+
+const LEFT_THUMB: [(f64, f64, f64, f64); 6] = [
+    (1.0, 0.0, 1.0, 1.0), (2.0, 0.0, 1.0, 1.0), (2.0, 1.0, 1.0, 1.0),
+    (0.0, 1.0, 1.0, 2.0), (1.0, 1.0, 1.0, 2.0), (2.0, 2.0, 1.0, 1.0)
+];
+
+const RIGHT_MAIN: [(f64, f64, f64, f64); 32] = [
+    // First row
+    (0.0, 0.0, 1.0, 1.0), (1.0, 0.0, 1.0, 1.0), (2.0, 0.0, 1.0, 1.0),
+    (3.0, 0.0, 1.0, 1.0), (4.0, 0.0, 1.0, 1.0), (5.0, 0.0, 1.0, 1.0),
+    (6.0, 0.0, 1.5, 1.0),
+    // Second row
+    (0.0, 1.0, 1.0, 1.5), (1.0, 1.0, 1.0, 1.0), (2.0, 1.0, 1.0, 1.0),
+    (3.0, 1.0, 1.0, 1.0), (4.0, 1.0, 1.0, 1.0), (5.0, 1.0, 1.0, 1.0),
+    (6.0, 1.0, 1.5, 1.0),
+    // Third row
+    (1.0, 2.0, 1.0, 1.0), (2.0, 2.0, 1.0, 1.0), (3.0, 2.0, 1.0, 1.0),
+    (4.0, 2.0, 1.0, 1.0), (5.0, 2.0, 1.0, 1.0), (6.0, 2.0, 1.5, 1.0),
+    // Fourth row
+    (0.0, 2.5, 1.0, 1.5), (1.0, 3.0, 1.0, 1.0), (2.0, 3.0, 1.0, 1.0),
+    (3.0, 3.0, 1.0, 1.0), (4.0, 3.0, 1.0, 1.0), (5.0, 3.0, 1.0, 1.0),
+    (6.0, 3.0, 1.5, 1.0),
+    // Fifth row
+    (2.0, 4.0, 1.0, 1.0), (3.0, 4.0, 1.0, 1.0), (4.0, 4.0, 1.0, 1.0),
+    (5.0, 4.0, 1.0, 1.0), (6.0, 4.0, 1.0, 1.0)
+];
+
+const RIGHT_THUMB: [(f64, f64, f64, f64); 6] = [
+    (0.0, 0.0, 1.0, 1.0), (1.0, 0.0, 1.0, 1.0), (0.0, 1.0, 1.0, 1.0),
+    (0.0, 2.0, 1.0, 1.0), (1.0, 1.0, 1.0, 2.0), (2.0, 1.0, 1.0, 2.0)
+];
+
+// Vertical offset of each finger column, as (columns starting before x,
+// offset) relative to the main block
+const LEFT_STAGGER: [(f64, f64); 5] = [
+    (2.5, 0.375), (3.5, 0.125), (4.5, 0.0), (5.5, 0.125), (f64::INFINITY, 0.25)
+];
+
+const RIGHT_STAGGER: [(f64, f64); 5] = [
+    (2.0, 0.25), (3.0, 0.125), (4.0, 0.0), (5.0, 0.125), (f64::INFINITY, 0.375)
+];
+
+// Thumb clusters tilt towards the palms by this many degrees
+const THUMB_ANGLE: f64 = 25.0;
+
+fn cluster(keys: &Cluster, dx: f64, dy: f64) -> Vec<KeyPosition> {
+    keys.iter()
+        .map(|&(x, y, w, h)| KeyPosition {
+            x: x + dx, y: y + dy, w, h,
+            r: 0.0, rx: 0.0, ry: 0.0
+        })
+        .collect()
+}
+
+fn staggered(keys: &Cluster, stagger: &[(f64, f64)]) -> Vec<(f64, f64, f64, f64)> {
+    keys.iter()
+        .map(|&(x, y, w, h)| {
+            let offset = stagger.iter()
+                .find(|&&(before, _)| x < before)
+                .map_or(0.0, |&(_, offset)| offset);
+            (x, y + offset, w, h)
+        })
+        .collect()
+}
+
+fn rotated(keys: Vec<KeyPosition>, r: f64, rx: f64, ry: f64) -> Vec<KeyPosition> {
+    keys.into_iter()
+        .map(|k| KeyPosition{ r, rx, ry, ..k })
+        .collect()
+}
+
+fn number(props: &Map<String, Value>, name: &str) -> Option<f64> {
+    props.get(name).and_then(Value::as_f64)
+}
+
+impl KeyPosition {
+    /// Corners of the key after rotation, in key units
+    pub fn corners(&self) -> [(f64, f64); 4] {
+        let (sin, cos) = self.r.to_radians().sin_cos();
+        let rotate = |x: f64, y: f64| {
+            let (dx, dy) = (x - self.rx, y - self.ry);
+            (self.rx + dx * cos - dy * sin,
+             self.ry + dx * sin + dy * cos)
+        };
+        [rotate(self.x, self.y),
+         rotate(self.x + self.w, self.y),
+         rotate(self.x + self.w, self.y + self.h),
+         rotate(self.x, self.y + self.h)]
+    }
+}
+
+impl Geometry {
+
+    /// The ErgoDox as the KEYMAP macro lists it: left hand, left thumb,
+    /// right hand, right thumb. Thumb clusters are angled as on the
+    /// physical board.
+    pub fn ergodox() -> Geometry {
+        Geometry::ergodox_angled(false)
+    }
+
+    /// The ErgoDox with angled thumbs and the column stagger of the
+    /// physical board.
+    pub fn ergodox_staggered() -> Geometry {
+        Geometry::ergodox_angled(true)
+    }
+
+    /// The ErgoDox drawn as axis-aligned blocks.
+    pub fn ergodox_flat() -> Geometry {
+        let mut keys = cluster(&LEFT_MAIN, 0.0, 0.0);
+        keys.extend(cluster(&LEFT_THUMB, 6.75, 3.25));
+        keys.extend(cluster(&RIGHT_MAIN, 12.5, 0.0));
+        keys.extend(cluster(&RIGHT_THUMB, 10.0, 3.25));
+        Geometry{ keys }
+    }
+
+    fn ergodox_angled(stagger: bool) -> Geometry {
+        let (left, right, top) = if stagger {
+            // The inner columns sit lower, so the thumbs move down too
+            (staggered(&LEFT_MAIN, &LEFT_STAGGER), staggered(&RIGHT_MAIN, &RIGHT_STAGGER), 3.5)
+        } else {
+            (LEFT_MAIN.to_vec(), RIGHT_MAIN.to_vec(), 3.25)
+        };
+
+        // Each thumb cluster pivots on its outer edge, level with the top
+        // of its outermost column of tall keys
+        let mut keys = cluster(&left, 0.0, 0.0);
+        keys.extend(rotated(cluster(&LEFT_THUMB, 6.5, top), THUMB_ANGLE, 6.5, top + 1.0));
+        keys.extend(cluster(&right, 12.5, 0.0));
+        keys.extend(rotated(cluster(&RIGHT_THUMB, 10.5, top), -THUMB_ANGLE, 13.5, top + 1.0));
+        Geometry{ keys }
+    }
+
+    /// One of the built in boards: ergodox, ergodox-staggered or
+    /// ergodox-flat.
+    pub fn named(name: &str) -> Option<Geometry> {
+        match name {
+            "ergodox" => Some(Geometry::ergodox()),
+            "ergodox-staggered" => Some(Geometry::ergodox_staggered()),
+            "ergodox-flat" => Some(Geometry::ergodox_flat()),
+            _ => None
+        }
+    }
+
+    /// Read keyboard-layout-editor raw data. Keys are taken in the
+    /// order they appear and decals are skipped.
+    pub fn from_kle(input: &str) -> Result<Geometry, String> {
+        let json: Value = serde_json::from_str(input).map_err(|e| e.to_string())?;
+        let rows = json.as_array().ok_or("KLE data must be an array of rows")?;
+
+        let mut keys = Vec::new();
+        let (mut r, mut rx, mut ry) = (0.0, 0.0, 0.0);
+        let mut y = 0.0;
+
+        // The first entry may be keyboard metadata rather than a row
+        for row in rows.iter().filter_map(Value::as_array) {
+            let mut x = rx;
+            let (mut w, mut h) = (1.0, 1.0);
+            let mut decal = false;
+
+            for item in row {
+                match *item {
+                    Value::Object(ref props) => {
+                        if let Some(v) = number(props, "r") { r = v; }
+                        if let Some(v) = number(props, "rx") { rx = v; x = rx; y = ry; }
+                        if let Some(v) = number(props, "ry") { ry = v; x = rx; y = ry; }
+                        if let Some(v) = number(props, "x") { x += v; }
+                        if let Some(v) = number(props, "y") { y += v; }
+                        if let Some(v) = number(props, "w") { w = v; }
+                        if let Some(v) = number(props, "h") { h = v; }
+                        if let Some(d) = props.get("d").and_then(Value::as_bool) { decal = d; }
+                    }
+                    Value::String(_) => {
+                        if !decal {
+                            keys.push(KeyPosition{ x, y, w, h, r, rx, ry });
+                        }
+                        x += w;
+                        w = 1.0;
+                        h = 1.0;
+                        decal = false;
+                    }
+                    _ => return Err(format!("Unexpected KLE entry: {}", item))
+                }
+            }
+            y += 1.0;
+        }
+
+        if keys.is_empty() {
+            return Err(String::from("KLE data contains no keys"));
+        }
+        Ok(Geometry{ keys })
+    }
+
+    /// Read a layout from the `layouts` section of a QMK info.json. When
+    /// no layout is named, LAYOUT_ergodox is used if present and the
+    /// first layout by name otherwise.
+    pub fn from_qmk(input: &str, layout: Option<&str>) -> Result<Geometry, String> {
+        let json: Value = serde_json::from_str(input).map_err(|e| e.to_string())?;
+        let layouts = json.get("layouts")
+            .and_then(Value::as_object)
+            .ok_or("info.json has no layouts")?;
+
+        let name = match layout {
+            Some(name) => json.get("layout_aliases")
+                .and_then(|aliases| aliases.get(name))
+                .and_then(Value::as_str)
+                .unwrap_or(name),
+            None if layouts.contains_key("LAYOUT_ergodox") => "LAYOUT_ergodox",
+            None => layouts.keys().next().ok_or("info.json has no layouts")?
+        };
+
+        let entries = layouts.get(name)
+            .and_then(|l| l.get("layout"))
+            .and_then(Value::as_array)
+            .ok_or_else(|| format!("Layout {} not found", name))?;
+
+        let mut keys = Vec::new();
+        for entry in entries {
+            let props = entry.as_object()
+                .ok_or_else(|| format!("Unexpected layout entry: {}", entry))?;
+            let x = number(props, "x").ok_or_else(|| format!("Key without x: {}", entry))?;
+            let y = number(props, "y").ok_or_else(|| format!("Key without y: {}", entry))?;
+            keys.push(KeyPosition{
+                x, y,
+                w: number(props, "w").unwrap_or(1.0),
+                h: number(props, "h").unwrap_or(1.0),
+                r: number(props, "r").unwrap_or(0.0),
+                rx: number(props, "rx").unwrap_or(0.0),
+                ry: number(props, "ry").unwrap_or(0.0)
+            });
+        }
+
+        if keys.is_empty() {
+            return Err(format!("Layout {} contains no keys", name));
+        }
+        Ok(Geometry{ keys })
+    }
+
+    /// Read either KLE raw data or a QMK info.json, depending on the
+    /// shape of the document.
+    pub fn from_json(input: &str, layout: Option<&str>) -> Result<Geometry, String> {
+        if input.trim_start().starts_with('[') {
+            Geometry::from_kle(input)
+        } else {
+            Geometry::from_qmk(input, layout)
+        }
+    }
+
+    /// Bounding box of every key as (x, y, width, height) in key units
+    pub fn bounds(&self) -> (f64, f64, f64, f64) {
+        let corners: Vec<(f64, f64)> = self.keys.iter()
+            .flat_map(|k| k.corners().to_vec())
+            .collect();
+        if corners.is_empty() {
+            return (0.0, 0.0, 0.0, 0.0);
+        }
+        let min_x = corners.iter().map(|c| c.0).fold(f64::INFINITY, f64::min);
+        let min_y = corners.iter().map(|c| c.1).fold(f64::INFINITY, f64::min);
+        let max_x = corners.iter().map(|c| c.0).fold(f64::NEG_INFINITY, f64::max);
+        let max_y = corners.iter().map(|c| c.1).fold(f64::NEG_INFINITY, f64::max);
+        (min_x, min_y, max_x - min_x, max_y - min_y)
+    }
+}
+
+#[test]
+fn test_ergodox_geometry() {
+    let g = Geometry::ergodox_flat();
+    assert_eq!(g.keys.len(), 76);
+    assert_eq!(g.bounds(), (0.0, 0.0, 20.0, 6.25));
+}
+
+#[test]
+fn test_ergodox_keys_do_not_overlap() {
+    // Separating axis test on the rotated key outlines
+    fn separated(a: &[(f64, f64); 4], b: &[(f64, f64); 4]) -> bool {
+        [a, b].iter().any(|poly| (0..4).any(|i| {
+            let (p, q) = (poly[i], poly[(i + 1) % 4]);
+            let axis = (q.1 - p.1, p.0 - q.0);
+            let project = |c: &[(f64, f64); 4]| {
+                let ds: Vec<f64> = c.iter().map(|v| v.0 * axis.0 + v.1 * axis.1).collect();
+                (ds.iter().cloned().fold(f64::INFINITY, f64::min),
+                 ds.iter().cloned().fold(f64::NEG_INFINITY, f64::max))
+            };
+            let ((amin, amax), (bmin, bmax)) = (project(a), project(b));
+            amax <= bmin + 1e-9 || bmax <= amin + 1e-9
+        }))
+    }
+
+    for g in &[Geometry::ergodox(), Geometry::ergodox_staggered(), Geometry::ergodox_flat()] {
+        assert_eq!(g.keys.len(), 76);
+        let corners: Vec<_> = g.keys.iter().map(KeyPosition::corners).collect();
+        for i in 0..corners.len() {
+            for j in i + 1..corners.len() {
+                assert!(separated(&corners[i], &corners[j]), "keys {} and {} overlap", i, j);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_kle_rows() {
+    let g = Geometry::from_kle(r#"[{"name":"test"},
+        ["Q", {"w":1.5}, "W", "E"],
+        [{"x":0.25, "y":0.5, "h":2}, "A", {"d":true}, "note", "S"]]"#).unwrap();
+    let xywh: Vec<_> = g.keys.iter().map(|k| (k.x, k.y, k.w, k.h)).collect();
+    assert_eq!(xywh, vec![(0.0, 0.0, 1.0, 1.0),
+                          (1.0, 0.0, 1.5, 1.0),
+                          (2.5, 0.0, 1.0, 1.0),
+                          (0.25, 1.5, 1.0, 2.0),
+                          (2.25, 1.5, 1.0, 1.0)]);
+}
+
+#[test]
+fn test_kle_rotation() {
+    let g = Geometry::from_kle(r#"[
+        [{"r":30, "rx":6.5, "ry":4.25, "y":-1, "x":1}, "A"],
+        ["B"]]"#).unwrap();
+    assert_eq!(g.keys[0], KeyPosition{ x: 7.5, y: 3.25, w: 1.0, h: 1.0, r: 30.0, rx: 6.5, ry: 4.25 });
+    assert_eq!((g.keys[1].x, g.keys[1].y, g.keys[1].r), (6.5, 4.25, 30.0));
+}
+
+#[test]
+fn test_qmk_layout() {
+    let info = r#"{
+        "keyboard_name": "test",
+        "layout_aliases": {"KEYMAP": "LAYOUT_ergodox"},
+        "layouts": {
+            "LAYOUT_a": {"layout": [{"x": 9, "y": 9}]},
+            "LAYOUT_ergodox": {"layout": [
+                {"label": "k00", "matrix": [0, 0], "x": 0, "y": 0, "w": 1.5},
+                {"label": "k01", "matrix": [0, 1], "x": 1.5, "y": 0.25, "h": 2, "r": 25, "rx": 2, "ry": 1}
+            ]}
+        }
+    }"#;
+    let g = Geometry::from_qmk(info, None).unwrap();
+    assert_eq!(g.keys, vec![
+        KeyPosition{ x: 0.0, y: 0.0, w: 1.5, h: 1.0, r: 0.0, rx: 0.0, ry: 0.0 },
+        KeyPosition{ x: 1.5, y: 0.25, w: 1.0, h: 2.0, r: 25.0, rx: 2.0, ry: 1.0 }
+    ]);
+    assert_eq!(Geometry::from_json(info, Some("KEYMAP")).unwrap(), g);
+    assert_eq!(Geometry::from_qmk(info, Some("LAYOUT_a")).unwrap().keys[0].x, 9.0);
+    assert!(Geometry::from_qmk(info, Some("LAYOUT_b")).is_err());
+}
@@ -0,0 +1,279 @@
+use std::collections::{BTreeSet, VecDeque};
+
+use types::*;
+use resolve::*;
+use render::target_layer;
+
+/// How a key moves between layers
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum EdgeKind {
+    Momentary,
+    TapKey,
+    TapToggle,
+    Toggle,
+    Set,
+    SetClear,
+    Default
+}
+
+impl EdgeKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            EdgeKind::Momentary => "momentary",
+            EdgeKind::TapKey => "tap key",
+            EdgeKind::TapToggle => "tap toggle",
+            EdgeKind::Toggle => "toggle",
+            EdgeKind::Set => "set",
+            EdgeKind::SetClear => "set clear",
+            EdgeKind::Default => "default"
+        }
+    }
+
+    /// Whether the layer goes away again when the key is let go
+    pub fn is_momentary(&self) -> bool {
+        matches!(self, EdgeKind::Momentary | EdgeKind::TapKey | EdgeKind::TapToggle | EdgeKind::SetClear)
+    }
+
+    /// The kind of switch an action makes, and the layer it switches to.
+    /// The layer comes from `target_layer`, as the diagram shows it.
+    pub fn of(action: &Action) -> Option<(EdgeKind, u32)> {
+        let kind = match action {
+            Action::LayerMomentary(_) => EdgeKind::Momentary,
+            Action::LayerTapKey(..) => EdgeKind::TapKey,
+            Action::LayerTapToggle(_) => EdgeKind::TapToggle,
+            Action::LayerToggle(_) => EdgeKind::Toggle,
+            Action::LayerSet(..) => EdgeKind::Set,
+            Action::LayerSetClear(_) => EdgeKind::SetClear,
+            Action::DefaultLayerSet(_) => EdgeKind::Default,
+            Action::Function(_) | Action::FunctionTap(_) |
+            Action::ModsKey(..) | Action::ModsTapKey(..) => return None
+        };
+        target_layer(action).map(|layer| (kind, layer))
+    }
+}
+
+/// A key on one layer that switches to another
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Edge {
+    pub from: usize,
+    pub to: usize,
+    pub kind: EdgeKind,
+    /// The key as written in the keymap, such as FN1
+    pub key: String
+}
+
+/// Which layers lead to which
+#[derive(Debug, Clone, PartialEq)]
+pub struct LayerGraph {
+    pub layers: usize,
+    pub edges: Vec<Edge>,
+    /// Layers that can be turned on for good but have no key to leave
+    pub traps: Vec<usize>
+}
+
+// The layer state once an action has been pressed and released
+fn after(action: &Action, state: LayerState) -> LayerState {
+    let mut state = state;
+    match action {
+        Action::LayerToggle(l) => state.toggle(*l),
+        Action::LayerSet(l, _) => state.only(*l),
+        Action::LayerSetClear(_) => state.layers = 0,
+        Action::DefaultLayerSet(l) => state.default = *l,
+        _ => ()
+    }
+    state
+}
+
+/// The layer an action turns on for good, and the layer state it
+/// leaves behind when pressed on the base layer
+pub fn latched(action: &Action) -> Option<(u32, LayerState)> {
+    let mut state = LayerState::new(0);
+    match action {
+        Action::LayerToggle(l) => {
+            state.on(*l);
+            Some((*l, state))
+        }
+        Action::LayerSet(l, _) => {
+            state.only(*l);
+            Some((*l, state))
+        }
+        Action::DefaultLayerSet(l) => Some((*l, LayerState::new(*l))),
+        _ => None
+    }
+}
+
+/// Whether some key, pressed alone or while holding a momentary layer
+/// key, turns `layer` off again once it has been `entered`
+pub fn can_leave(keymaps: &KeyMapVec, actions: &ActionMap, entered: LayerState, layer: u32) -> bool {
+    let mut states = vec![entered];
+    for resolved in effective_keymap(keymaps, actions, entered) {
+        match resolved.action {
+            Some(Action::LayerMomentary(l)) | Some(Action::LayerTapKey(l, _)) |
+            Some(Action::LayerTapToggle(l)) => {
+                let mut held = entered;
+                held.on(*l);
+                states.push(held);
+            }
+            _ => ()
+        }
+    }
+    states.into_iter().any(|state| {
+        effective_keymap(keymaps, actions, state).iter()
+            .filter_map(|resolved| resolved.action)
+            .any(|action| !after(action, entered).is_on(layer))
+    })
+}
+
+impl LayerGraph {
+    /// The switches on each layer, counting keys that fall through to
+    /// the layers below as a diagram of the layer shows them
+    pub fn new(keymaps: &KeyMapVec, actions: &ActionMap) -> LayerGraph {
+        let mut edges = BTreeSet::new();
+        let mut traps = BTreeSet::new();
+        for layer in 0..keymaps.len() {
+            for resolved in effective_keymap(keymaps, actions, LayerState::up_to(layer as u32)) {
+                let action = match resolved.action {
+                    Some(action) => action,
+                    None => continue
+                };
+                let (kind, to) = match EdgeKind::of(action) {
+                    Some(edge) => edge,
+                    None => continue
+                };
+                if to as usize != layer {
+                    edges.insert(Edge{ from: layer, to: to as usize, kind, key: resolved.key.to_string() });
+                }
+                if let Some((to, state)) = latched(action) {
+                    if to != 0 && (to as usize) < keymaps.len() && !can_leave(keymaps, actions, state, to) {
+                        traps.insert(to as usize);
+                    }
+                }
+            }
+        }
+        LayerGraph{ layers: keymaps.len(), edges: edges.into_iter().collect(), traps: traps.into_iter().collect() }
+    }
+
+    /// Layers that some chain of keys leads to from the base layer
+    pub fn reachable(&self) -> BTreeSet<usize> {
+        let mut seen = BTreeSet::new();
+        let mut queue = VecDeque::new();
+        if self.layers > 0 {
+            seen.insert(0);
+            queue.push_back(0);
+        }
+        while let Some(layer) = queue.pop_front() {
+            for edge in self.edges.iter().filter(|e| e.from == layer && e.to < self.layers) {
+                if seen.insert(edge.to) {
+                    queue.push_back(edge.to);
+                }
+            }
+        }
+        seen
+    }
+
+    pub fn unreachable(&self) -> Vec<usize> {
+        let reachable = self.reachable();
+        (0..self.layers).filter(|l| !reachable.contains(l)).collect()
+    }
+
+    // Every layer drawn, including those switched to but missing
+    fn nodes(&self) -> BTreeSet<usize> {
+        (0..self.layers).chain(self.edges.iter().map(|e| e.to)).collect()
+    }
+
+    fn label(&self, layer: usize) -> String {
+        if layer < self.layers {
+            format!("Layer {}", layer)
+        } else {
+            format!("Layer {} (missing)", layer)
+        }
+    }
+
+    /// One line for each problem found
+    pub fn problems(&self) -> Vec<String> {
+        self.unreachable().into_iter()
+            .map(|l| format!("Layer {} cannot be reached from layer 0", l))
+            .chain(self.traps.iter().map(|l| format!("Layer {} can be turned on but not off", l)))
+            .chain(self.nodes().into_iter().filter(|&l| l >= self.layers)
+                   .map(|l| format!("Layer {} is switched to but does not exist", l)))
+            .collect()
+    }
+
+    /// The graph in Graphviz's DOT language. Momentary switches are
+    /// dashed, unreachable layers grey and traps red.
+    pub fn to_dot(&self) -> String {
+        let unreachable = self.unreachable();
+        let mut dot = String::from("digraph layers {\n    rankdir=LR;\n    node [shape=box];\n");
+        for layer in self.nodes() {
+            let mut attrs = format!("label=\"{}\"", self.label(layer));
+            if self.traps.contains(&layer) || layer >= self.layers {
+                attrs.push_str(", color=red");
+            }
+            if unreachable.contains(&layer) {
+                attrs.push_str(", style=dashed, fontcolor=grey");
+            }
+            dot.push_str(&format!("    layer{} [{}];\n", layer, attrs));
+        }
+        for edge in &self.edges {
+            let style = if edge.kind.is_momentary() { ", style=dashed" } else { "" };
+            dot.push_str(&format!("    layer{} -> layer{} [label=\"{} {}\"{}];\n",
+                                  edge.from, edge.to, edge.key, edge.kind.name(), style));
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// The graph as a Mermaid flowchart, drawn as `to_dot` draws it
+    pub fn to_mermaid(&self) -> String {
+        let unreachable = self.unreachable();
+        let mut mermaid = String::from("graph LR\n");
+        for layer in self.nodes() {
+            mermaid.push_str(&format!("    layer{}[\"{}\"]\n", layer, self.label(layer)));
+        }
+        for edge in &self.edges {
+            let arrow = if edge.kind.is_momentary() { "-.->" } else { "-->" };
+            mermaid.push_str(&format!("    layer{} {}|\"{} {}\"| layer{}\n",
+                                      edge.from, arrow, edge.key, edge.kind.name(), edge.to));
+        }
+        for layer in self.nodes() {
+            if self.traps.contains(&layer) || layer >= self.layers {
+                mermaid.push_str(&format!("    class layer{} trap\n", layer));
+            } else if unreachable.contains(&layer) {
+                mermaid.push_str(&format!("    class layer{} unreachable\n", layer));
+            }
+        }
+        mermaid.push_str("    classDef trap stroke:#d00,stroke-width:2px\n");
+        mermaid.push_str("    classDef unreachable stroke-dasharray:5 5,color:#888\n");
+        mermaid
+    }
+}
+
+#[test]
+fn test_layer_graph() {
+    let (keymaps, actions) = ::parser::parse_test_keymap("KEYMAP(FN0, FN1, A), KEYMAP(TRNS, TRNS, FN2),\n\
+                                                          KEYMAP(B, C, D), KEYMAP(E, F, G)",
+                                                         "[0] = ACTION_LAYER_MOMENTARY(1),\n\
+                                                          [1] = ACTION_LAYER_TOGGLE(1),\n\
+                                                          [2] = ACTION_LAYER_SET(2, ON_PRESS),\n\
+                                                          [3] = ACTION_LAYER_TOGGLE(7)");
+
+    let graph = LayerGraph::new(&keymaps, &actions);
+    let edges: Vec<(usize, usize, &str, &str)> = graph.edges.iter()
+        .map(|e| (e.from, e.to, e.key.as_str(), e.kind.name()))
+        .collect();
+    assert_eq!(edges, vec![(0, 1, "FN0", "momentary"), (0, 1, "FN1", "toggle"), (1, 2, "FN2", "set")]);
+    assert_eq!(graph.unreachable(), vec![3]);
+    assert_eq!(graph.traps, vec![2]);
+    assert_eq!(graph.problems(), vec!["Layer 3 cannot be reached from layer 0",
+                                      "Layer 2 can be turned on but not off"]);
+
+    let dot = graph.to_dot();
+    assert!(dot.contains("    layer0 -> layer1 [label=\"FN0 momentary\", style=dashed];\n"));
+    assert!(dot.contains("    layer2 [label=\"Layer 2\", color=red];\n"));
+    assert!(dot.contains("    layer3 [label=\"Layer 3\", style=dashed, fontcolor=grey];\n"));
+
+    let mermaid = graph.to_mermaid();
+    assert!(mermaid.starts_with("graph LR\n    layer0[\"Layer 0\"]\n"));
+    assert!(mermaid.contains("    layer1 -->|\"FN2 set\"| layer2\n"));
+    assert!(mermaid.contains("    class layer3 unreachable\n"));
+}
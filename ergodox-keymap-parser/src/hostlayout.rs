@@ -0,0 +1,100 @@
+use std::collections::HashMap;
+use toml;
+
+use keycodes;
+
+/// The characters a key types at each shift level
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Levels {
+    pub normal: String,
+    pub shifted: String,
+    pub altgr: String
+}
+
+/// The layout the operating system is set to, which decides what each
+/// keycode types. Keycodes that are not listed type what their US
+/// QWERTY legend shows.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HostLayout {
+    pub name: String,
+    keys: HashMap<String, Levels>
+}
+
+impl HostLayout {
+    /// One of the bundled layouts: us, uk, de, fr, dvorak or colemak
+    pub fn named(name: &str) -> Option<HostLayout> {
+        let data = match name {
+            "us" => include_str!("data/layouts/us.toml"),
+            "uk" => include_str!("data/layouts/uk.toml"),
+            "de" => include_str!("data/layouts/de.toml"),
+            "fr" => include_str!("data/layouts/fr.toml"),
+            "dvorak" => include_str!("data/layouts/dvorak.toml"),
+            "colemak" => include_str!("data/layouts/colemak.toml"),
+            _ => return None
+        };
+        Some(HostLayout::from_toml(data).unwrap_or_else(|e| panic!("Bad {} layout: {}", name, e)))
+    }
+
+    /// Read a layout from TOML. Each entry under `[keys]` lists the
+    /// normal, shifted and AltGr characters of a keycode; the last two
+    /// may be left out.
+    pub fn from_toml(input: &str) -> Result<HostLayout, String> {
+        let file: LayoutFile = toml::from_str(input).map_err(|e| e.to_string())?;
+
+        let mut keys = HashMap::new();
+        for (name, levels) in file.keys {
+            if levels.is_empty() || levels.len() > 3 {
+                return Err(format!("{} needs one to three characters", name));
+            }
+            let level = |n: usize| levels.get(n).cloned().unwrap_or_default();
+            // Keycodes are stored under their short name, so any alias finds them
+            keys.insert(keycodes::canonical(&name), Levels{ normal: level(0), shifted: level(1), altgr: level(2) });
+        }
+        Ok(HostLayout{ name: file.name, keys })
+    }
+
+    /// What a keycode types, if the layout moves it from US QWERTY
+    pub fn levels(&self, keycode: &str) -> Option<&Levels> {
+        self.keys.get(&keycodes::canonical(keycode))
+    }
+
+    /// What a keycode types with Shift held, if the layout moves it.
+    /// Keys with no shifted character, such as letters, type their own.
+    pub fn shifted(&self, keycode: &str) -> Option<&str> {
+        self.levels(keycode)
+            .map(|l| if l.shifted.is_empty() { l.normal.as_str() } else { l.shifted.as_str() })
+    }
+}
+
+// A layout as written in TOML
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct LayoutFile {
+    name: String,
+    keys: HashMap<String, Vec<String>>
+}
+
+#[test]
+fn test_host_layouts() {
+    for name in &["us", "uk", "de", "fr", "dvorak", "colemak"] {
+        assert!(HostLayout::named(name).is_some(), "{}", name);
+    }
+    assert_eq!(HostLayout::named("klingon"), None);
+
+    let de = HostLayout::named("de").unwrap();
+    assert_eq!(de.levels("KC_Z").map(|l| l.normal.as_str()), Some("Y"));
+    assert_eq!(de.levels("KC_LBRACKET").map(|l| l.normal.as_str()), Some("Ü"));
+    assert_eq!(de.levels("KC_Q"), Some(&Levels{
+        normal: String::from("Q"),
+        shifted: String::new(),
+        altgr: String::from("@")
+    }));
+    assert_eq!(de.levels("KC_A"), None);
+    assert_eq!(de.shifted("KC_9"), Some(")"));
+    assert_eq!(de.shifted("KC_LBRC"), Some("Ü"));
+
+    let fr = HostLayout::named("fr").unwrap();
+    assert_eq!(fr.levels("KC_1").map(|l| (l.normal.as_str(), l.shifted.as_str())), Some(("&", "1")));
+
+    assert!(HostLayout::from_toml("name = \"x\"\n[keys]\nA = []").is_err());
+}
@@ -1,16 +1,28 @@
+use std::collections::BTreeSet;
+
 use svg::Document;
 use svg::node::element::*;
-use svg::node::{Text as TextContent};
+use svg::node::{Node, Text as TextContent};
 
-use types::*;
-use std::ops::Range;
+use render::*;
+use options::*;
+use theme::*;
+use category::Category;
+use legend::*;
 
-pub struct Keyboard {
-    keymaps: KeyMapVec,
-    actions: ActionMap
-}
+// Size of a 1u key in SVG user units
+const UNIT: f64 = 100.0;
+
+// Space between layers when they are drawn one below the other
+const LAYER_GAP: f64 = 50.0;
+
+// Width of one entry of the category legend and height of its rows
+const LEGEND_ENTRY: f64 = 300.0;
+const LEGEND_ROW: f64 = 60.0;
+
+fn drawkey(width: f64, height: f64, theme: &Theme) -> Group {
+    let (inside_x, inside_y, inside_width, inside_height) = inside(width, height);
 
-fn drawkey(width: f64, height: f64) -> Group {
     let background = Rectangle::new()
         .set("x", 1)
         .set("y", 1)
@@ -18,8 +30,8 @@ fn drawkey(width: f64, height: f64) -> Group {
         .set("height", height-2.0)
         .set("rx", 15)
         .set("ry", 15)
-        .set("stroke","white")
-        .set("fill","white");
+        .set("stroke", theme.key_background.as_str())
+        .set("fill", theme.key_background.as_str());
 
     let outside = Rectangle::new()
         .set("x", 1)
@@ -28,18 +40,20 @@ fn drawkey(width: f64, height: f64) -> Group {
         .set("height", height-2.0)
         .set("rx", 15)
         .set("ry", 15)
-        .set("stroke", "#A5A5A5")
-        .set("fill", "url(#keyoutside)");
+        .set("stroke", theme.key_stroke.as_str())
+        .set("fill", "url(#keyoutside)")
+        .set("class", "outside");
 
     let inside = Rectangle::new()
-        .set("x", 10)
-        .set("y", 7)
-        .set("width", width-20.0)
-        .set("height", height-20.0)
+        .set("x", inside_x)
+        .set("y", inside_y)
+        .set("width", inside_width)
+        .set("height", inside_height)
         .set("rx", 10)
         .set("ry", 10)
-        .set("stroke", "#F9F9F9")
-        .set("fill", "url(#keyinside)");
+        .set("stroke", theme.inside_stroke.as_str())
+        .set("fill", "url(#keyinside)")
+        .set("class", "inside");
 
     Group::new()
         .add(background)
@@ -48,383 +62,298 @@ fn drawkey(width: f64, height: f64) -> Group {
  }
 
 
-pub enum KeyShape {
-    K10u,
-    K15h,
-    K20v,
-    K15v
-}
-macro_rules! makeKeyGroup {
-    ($key:path, $code:expr, $x:expr, $y:expr) => {
-        {
-            let (w,h) = match $key {
-                KeyShape::K10u => (100.0, 100.0),
-                KeyShape::K15h => (150.0, 100.0),
-                KeyShape::K15v => (100.0, 150.0),
-                KeyShape::K20v => (100.0, 200.0)
-            };
-
-            drawkey(w,h)
-                .set("transform", format!("translate({},{})",$x, $y))
-        }
-    }
-}
-
-fn textoutput(input: &str) -> (String,String) {
-    let input = input.replace("KC_","");
-    let input = input.replace("MOD_","");
-    let (normal, shifted) =
-        match input.as_str() {
-            "NO"   => ("",""),
-            "EQL"  => ("=","+"),
-            "RGHT" => ("→",""),
-            "LEFT" => ("←",""),
-            "UP"   => ("↑",""),
-            "DOWN" => ("↓",""),
-            "COMM" => (",","<"),
-            "DOT"  => (".", ">"),
-            "QUOT" => ("'", "\""),
-            "MINS" => ("-", "_"),
-            "BSLS" => ("\\","|"),
-            "SLSH" => ("/","?"),
-            "GRV"  => ("`","~"),
-            "SCLN" => (";",":"),
-            "ENT" | "PENT" => ("⏎",""),
-            "LBRC" => ("[","{"),
-            "RBRC" => ("]","}"),
-            "SPC" => ("␣",""),
-            "0" => ("0",")"),
-            "1" => ("1","!"),
-            "2" => ("2","@"),
-            "3" => ("3","#"),
-            "4" => ("4","$"),
-            "5" => ("5","%"),
-            "6" => ("6","^"),
-            "7" => ("7","&"),
-            "8" => ("8","*"),
-            "9" => ("9","("),
-            "F1" => ("F1",""),
-            "F2" => ("F2",""),
-            "F3" => ("F3",""),
-            "F4" => ("F4",""),
-            "F5" => ("F5",""),
-            "F6" => ("F6",""),
-            "F7" => ("F7",""),
-            "F8" => ("F8",""),
-            "F9" => ("F9",""),
-            "F10" => ("F10",""),
-            "F11" => ("F11",""),
-            "F12" => ("F12",""),
-            "F13" => ("F13",""),
-            "F14" => ("F14",""),
-            "F15" => ("F15",""),
-            "F16" => ("F16",""),
-            "F17" => ("F17",""),
-            "F18" => ("F18",""),
-            "F19" => ("F19",""),
-            "F20" => ("F20",""),
-            "F21" => ("F21",""),
-            "F22" => ("F22",""),
-            "F23" => ("F23",""),
-            "F24" => ("F24",""),
-            x => (x  , "")
-        };
-
-    (String::from(normal), String::from(shifted))
-}
-
 fn cdata(input: String) -> String {
     format!("<![CDATA[{}]]>",input)
 }
 
-macro_rules! addKeyText{
-    ($group:expr, $name:expr, $offset:expr) => {{
-        let (normal,shifted) = textoutput($name.as_ref());
-        $group = $group.add(Text::new()
-                            .set("x",50.0)
-                            .set("y",$offset)
-                            .set("class","shifted")
-                            .add(TextContent::new(cdata(shifted))));
-        $group = $group.add(Text::new()
-                            .set("x", 50.0)
-                            .set("y", $offset+25.0)
-                            .set("id", $name)
-                            .set("class","normal")
-                            .add(TextContent::new(cdata(normal)))
-        ).set("id", $name)
-    }};
-    ($group:expr, $name:expr) => {
-        addKeyText!($group, $name, 25.0)
-    }
+// One line of a wrapped legend. The svg crate has no tspan element.
+fn tspan(x: f64, dy: f64, line: &str) -> Element {
+    let mut tspan = Element::new("tspan");
+    tspan.assign("x", x);
+    tspan.assign("dy", dy);
+    tspan.append(TextContent::new(cdata(String::from(line))));
+    tspan
 }
 
-macro_rules! addLayer {
-    ($group:expr, $layer:expr, $function:expr) => {
-        $group = $group.set("onclick", format!("{}({})",$function,$layer))
-    };
-    ($group:expr, $layer:expr) => {
-        addLayer!($group, $layer, "layeron")
-    }
+macro_rules! addKeyText{
+    ($group:expr, $legend:expr, $placement:expr) => {{
+        let text = Text::new()
+            .set("x", $placement.x)
+            .set("y", $placement.y)
+            .set("font-size", $placement.size)
+            .set("text-anchor", $placement.anchor.name())
+            .set("id", $legend.name.as_str())
+            .set("class", $legend.slot.name());
+        let text = match $placement.lines.as_slice() {
+            [line] => text.add(TextContent::new(cdata(line.clone()))),
+            lines => lines.iter().enumerate().fold(text, |text, (n, line)| {
+                let dy = if n == 0 { 0.0 } else { $placement.line_height };
+                text.add(tspan($placement.x, dy, line))
+            })
+        };
+        $group = $group.add(text).set("id", $legend.name.as_str())
+    }}
 }
 
-macro_rules! addMomentaryLayer{
-    ($group:expr, $layer:expr) => {
-        $group = $group.set("onmousedown",
-                            format!("layeron({})",$layer))
-            .set("onmouseup",
-                 format!("layeroff({})",$layer))
-    }
+/// Draws every layer as a group of an interactive SVG document
+pub struct SvgRenderer {
+    options: RenderOptions,
+    // Bounds of a single layer
+    bounds: (f64, f64, f64, f64),
+    layers: Vec<Group>,
+    current: Group,
+    // Categories of the keys drawn so far
+    categories: BTreeSet<Category>,
+    // Layers drawn or switched to, which need accent colours
+    accented: BTreeSet<usize>
 }
 
-impl Keyboard {
-
-    pub fn new(keymaps: KeyMapVec, actions: ActionMap) -> Keyboard {
-        Keyboard{ keymaps: keymaps, actions: actions}
+impl SvgRenderer {
+    pub fn new() -> SvgRenderer {
+        SvgRenderer{
+            options: RenderOptions::default(),
+            bounds: (0.0, 0.0, 0.0, 0.0),
+            layers: Vec::new(),
+            current: Group::new(),
+            categories: BTreeSet::new(),
+            accented: BTreeSet::new()
+        }
     }
 
-    fn keynode(&self, x: f64, y: f64, layer: usize, keyn: usize, shape: KeyShape) -> Group {
-        let ref keycode = self.keymaps[layer][keyn];
-        let mut keygroup = makeKeyGroup!(shape, keycode, x, y);
-
-        match keycode {
-            &Key::Fx(action) =>
-                match self.actions.get(&action) {
-                    Some(act) =>
-                        match act {
-                            &Action::LayerSet(layer,_) => {
-                                addLayer!(keygroup, layer, "layeron");
-                                addKeyText!(keygroup, format!("#{}",layer))
-                            }
-                            &Action::LayerSetClear(layer) => {
-                                addLayer!(keygroup, layer, "onlylayer");
-                                addKeyText!(keygroup, format!("#{}!",layer))
-                            }
-                            &Action::LayerMomentary(layer) => {
-                                addMomentaryLayer!(keygroup,layer);
-                                addKeyText!(keygroup,format!("~{}",layer))
-                            }
-                            &Action::LayerTapKey(layer,ref k) => {
-                                addMomentaryLayer!(keygroup,layer);
-                                let s = match k {
-                                    &Key::Key(ref name) => name.as_str(),
-                                    _ => "WHAT?"
-                                };
-                                addKeyText!(keygroup, s);
-                                addKeyText!(keygroup, format!("~L{}",layer), 50.0)
-                            }
-                            &Action::ModsTapKey(ref m, ref k) => {
-                                let modifier = match m {
-                                    &Key::Key(ref name) => name.as_str(),
-                                    _ => "HUH?"
-                                };
-                                let k = match k {
-                                    &Key::Key(ref name) => name.as_str(),
-                                    _ => "HRM.."
-                                };
-                                addKeyText!(keygroup,modifier,0.0);
-                                addKeyText!(keygroup,k,50.0);
-                            }
-                            _ => ()
-                        },
-                    None => {
-                        addKeyText!(keygroup, "BROKEN",0.0);
-                    }
+    fn keynode(&self, key: &KeyView) -> Group {
+        let pos = key.position;
+
+        let translate = format!("translate({},{})", pos.x * UNIT, pos.y * UNIT);
+        let transform = if pos.r == 0.0 {
+            translate
+        } else {
+            format!("rotate({},{},{}) {}", pos.r, pos.rx * UNIT, pos.ry * UNIT, translate)
+        };
+        let mut keygroup = drawkey(pos.w * UNIT, pos.h * UNIT, &self.options.theme)
+            .set("transform", transform)
+            .set("class", {
+                let mut class = String::from(key.category.name());
+                if let Some(layer) = key.target {
+                    class.push_str(&format!(" to-layer{}", layer));
                 }
-            ,
-            &Key::Key(ref name) =>
-            {
-                addKeyText!(keygroup, name.as_str());
+                if key.inherited.is_some() {
+                    class.push_str(" inherited");
+                }
+                class
+            });
+
+        // The script finds keys by position, and follows their layer
+        // switches when no simulator is running the keymap
+        if self.options.script {
+            keygroup = keygroup.set("data-pos", key.index);
+            match key.switch {
+                Some(LayerSwitch::On(layer)) => keygroup = keygroup.set("data-switch", format!("on {}", layer)),
+                Some(LayerSwitch::Only(layer)) => keygroup = keygroup.set("data-switch", format!("only {}", layer)),
+                Some(LayerSwitch::Momentary(layer)) =>
+                    keygroup = keygroup.set("data-switch", format!("momentary {}", layer)),
+                None => ()
             }
         }
+
+        let placements = layout(&key.legends, pos.w * UNIT, pos.h * UNIT);
+        for (legend, placement) in key.legends.iter().zip(placements) {
+            addKeyText!(keygroup, legend, placement);
+        }
         keygroup
     }
 
-    fn leftthumb(self: &Keyboard, layer: usize) -> Group {
-    Group::new()
-        .add(self.keynode(100.0,  0.0,layer,32,KeyShape::K10u))
-        .add(self.keynode(200.0,  0.0,layer,33,KeyShape::K10u))
-        .add(self.keynode(200.0,100.0,layer,34,KeyShape::K10u))
-        .add(self.keynode(  0.0,100.0,layer,35,KeyShape::K20v))
-        .add(self.keynode(100.0,100.0,layer,36,KeyShape::K20v))
-        .add(self.keynode(200.0,200.0,layer,37,KeyShape::K10u))
+    fn defs(&self) -> Definitions {
+        let theme = &self.options.theme;
+
+        let keyoutside = LinearGradient::new()
+            .set("id", "keyoutside")
+            .set("x1", "0%")
+            .set("x2", "0%")
+            .set("y1", "0%")
+            .set("y2", "100%")
+            .add(Stop::new()
+                 .set("offset", "0%")
+                 .set("stop-color", theme.outside_top.as_str()))
+            .add(Stop::new()
+                 .set("offset", "100%")
+                 .set("stop-color", theme.outside_bottom.as_str()));
+
+        let keyinside = LinearGradient::new()
+            .set("id", "keyinside")
+            .set("x1", "0%")
+            .set("x2", "100%")
+            .set("y1", "0%")
+            .set("y2", "0%")
+            .add(Stop::new()
+                 .set("offset", "0%")
+                 .set("stop-color", theme.inside_edge.as_str()))
+            .add(Stop::new()
+                 .set("offset", "50%")
+                 .set("stop-color", theme.inside_middle.as_str()))
+            .add(Stop::new()
+                 .set("offset", "100%")
+                 .set("stop-color", theme.inside_edge.as_str()));
+
+        Definitions::new()
+            .add(keyoutside)
+            .add(keyinside)
     }
 
-    fn leftmain(self: &Keyboard, layer: usize) -> Group {
-        Group::new()
-        // First Row
-            // .set("onclick",format!("layer_click(evt,{})",layer))
-            .add(self.keynode(  0.0,  0.0,layer,0,KeyShape::K15h))
-            .add(self.keynode(150.0,  0.0,layer,1,KeyShape::K10u))
-            .add(self.keynode(250.0,  0.0,layer,2,KeyShape::K10u))
-            .add(self.keynode(350.0,  0.0,layer,3,KeyShape::K10u))
-            .add(self.keynode(450.0,  0.0,layer,4,KeyShape::K10u))
-            .add(self.keynode(550.0,  0.0,layer,5,KeyShape::K10u))
-            .add(self.keynode(650.0,  0.0,layer,6,KeyShape::K10u))
-        // Second Row
-            .add(self.keynode(  0.0,100.0,layer,7,KeyShape::K15h))
-            .add(self.keynode(150.0,100.0,layer,8,KeyShape::K10u))
-            .add(self.keynode(250.0,100.0,layer,9,KeyShape::K10u))
-            .add(self.keynode(350.0,100.0,layer,10,KeyShape::K10u))
-            .add(self.keynode(450.0,100.0,layer,11,KeyShape::K10u))
-            .add(self.keynode(550.0,100.0,layer,12,KeyShape::K10u))
-            .add(self.keynode(650.0,100.0,layer,13,KeyShape::K15v))
-        // Third Row
-            .add(self.keynode(  0.0,200.0,layer,14,KeyShape::K15h))
-            .add(self.keynode(150.0,200.0,layer,15,KeyShape::K10u))
-            .add(self.keynode(250.0,200.0,layer,16,KeyShape::K10u))
-            .add(self.keynode(350.0,200.0,layer,17,KeyShape::K10u))
-            .add(self.keynode(450.0,200.0,layer,18,KeyShape::K10u))
-            .add(self.keynode(550.0,200.0,layer,19,KeyShape::K10u))
-        // Fourth Row
-            .add(self.keynode(  0.0,300.0,layer,20,KeyShape::K15h))
-            .add(self.keynode(150.0,300.0,layer,21,KeyShape::K10u))
-            .add(self.keynode(250.0,300.0,layer,22,KeyShape::K10u))
-            .add(self.keynode(350.0,300.0,layer,23,KeyShape::K10u))
-            .add(self.keynode(450.0,300.0,layer,24,KeyShape::K10u))
-            .add(self.keynode(550.0,300.0,layer,25,KeyShape::K10u))
-            .add(self.keynode(650.0,250.0,layer,26,KeyShape::K15v))
-          // Fifth Row
-            .add(self.keynode( 50.0,400.0,layer,27,KeyShape::K10u))
-            .add(self.keynode(150.0,400.0,layer,28,KeyShape::K10u))
-            .add(self.keynode(250.0,400.0,layer,29,KeyShape::K10u))
-            .add(self.keynode(350.0,400.0,layer,30,KeyShape::K10u))
-            .add(self.keynode(450.0,400.0,layer,31,KeyShape::K10u))
+    // Categories that were drawn and have their own colour
+    fn coloured(&self) -> Vec<(Category, &str)> {
+        let fills = &self.options.theme.categories;
+        self.categories.iter()
+            .filter_map(|c| fills.get(c).map(|fill| (*c, fill.as_str())))
+            .collect()
     }
 
-    fn rightmain(self: &Keyboard, layer: usize) -> Group {
-        Group::new()
-        // first Row
-            .add(self.keynode(0.0,0.0,layer,38,KeyShape::K10u))
-            .add(self.keynode(100.0,0.0,layer,39,KeyShape::K10u))
-            .add(self.keynode(200.0,0.0,layer,40,KeyShape::K10u))
-            .add(self.keynode(300.0,0.0,layer,41,KeyShape::K10u))
-            .add(self.keynode(400.0,0.0,layer,42,KeyShape::K10u))
-            .add(self.keynode(500.0,0.0,layer,43,KeyShape::K10u))
-            .add(self.keynode(600.0,0.0,layer,44,KeyShape::K15h))
-        // Second row
-            .add(self.keynode(0.0,100.0,layer,45,KeyShape::K15v))
-            .add(self.keynode(100.0,100.0,layer,46,KeyShape::K10u))
-            .add(self.keynode(200.0,100.0,layer,47,KeyShape::K10u))
-            .add(self.keynode(300.0,100.0,layer,48,KeyShape::K10u))
-            .add(self.keynode(400.0,100.0,layer,49,KeyShape::K10u))
-            .add(self.keynode(500.0,100.0,layer,50,KeyShape::K10u))
-            .add(self.keynode(600.0,100.0,layer,51,KeyShape::K15h))
-        // Third row
-            .add(self.keynode(100.0,200.0,layer,52,KeyShape::K10u))
-            .add(self.keynode(200.0,200.0,layer,53,KeyShape::K10u))
-            .add(self.keynode(300.0,200.0,layer,54,KeyShape::K10u))
-            .add(self.keynode(400.0,200.0,layer,55,KeyShape::K10u))
-            .add(self.keynode(500.0,200.0,layer,56,KeyShape::K10u))
-            .add(self.keynode(600.0,200.0,layer,57,KeyShape::K15h))
-        // Fourth row
-            .add(self.keynode(0.0,250.0,layer,58,KeyShape::K15v))
-            .add(self.keynode(100.0,300.0,layer,59,KeyShape::K10u))
-            .add(self.keynode(200.0,300.0,layer,60,KeyShape::K10u))
-            .add(self.keynode(300.0,300.0,layer,61,KeyShape::K10u))
-            .add(self.keynode(400.0,300.0,layer,62,KeyShape::K10u))
-            .add(self.keynode(500.0,300.0,layer,63,KeyShape::K10u))
-            .add(self.keynode(600.0,300.0,layer,64,KeyShape::K15h))
-        // Fifth row
-            .add(self.keynode(200.0,400.0,layer,65,KeyShape::K10u))
-            .add(self.keynode(300.0,400.0,layer,66,KeyShape::K10u))
-            .add(self.keynode(400.0,400.0,layer,67,KeyShape::K10u))
-            .add(self.keynode(500.0,400.0,layer,68,KeyShape::K10u))
-            .add(self.keynode(600.0,400.0,layer,69,KeyShape::K10u))
+    // Swatches explaining the key colours, wrapped to the board width
+    fn legend(&self, top: f64) -> Group {
+        let (x, _, width, _) = self.bounds;
+        let columns = ((width / LEGEND_ENTRY) as usize).max(1);
+
+        self.coloured().into_iter().enumerate()
+            .fold(Group::new().set("class", "categories"), |group, (n, (category, fill))| {
+                let left = x + (n % columns) as f64 * LEGEND_ENTRY;
+                let y = top + (n / columns) as f64 * LEGEND_ROW;
+                group
+                    .add(Rectangle::new()
+                         .set("x", left)
+                         .set("y", y)
+                         .set("width", 40)
+                         .set("height", 40)
+                         .set("rx", 8)
+                         .set("ry", 8)
+                         .set("stroke", self.options.theme.key_stroke.as_str())
+                         .set("fill", fill))
+                    .add(Text::new()
+                         .set("x", left + 55.0)
+                         .set("y", y + 28.0)
+                         .add(TextContent::new(category.description())))
+            })
+    }
 
+    fn legend_height(&self) -> f64 {
+        let columns = ((self.bounds.2 / LEGEND_ENTRY) as usize).max(1);
+        let rows = self.coloured().len().div_ceil(columns);
+        if rows == 0 {
+            0.0
+        } else {
+            LAYER_GAP + rows as f64 * LEGEND_ROW
+        }
     }
+}
 
-    fn rightthumb(self: &Keyboard, layer: usize) -> Group {
-        Group::new()
-            .add(self.keynode(0.0,0.0,layer,70,KeyShape::K10u))
-            .add(self.keynode(100.0,0.0,layer,71,KeyShape::K10u))
-            .add(self.keynode(0.0,100.0,layer,72,KeyShape::K10u))
-            .add(self.keynode(0.0,200.0,layer,73,KeyShape::K10u))
-            .add(self.keynode(100.0,100.0,layer,74,KeyShape::K20v))
-            .add(self.keynode(200.0,100.0,layer,75,KeyShape::K20v))
+impl Default for SvgRenderer {
+    fn default() -> SvgRenderer {
+        SvgRenderer::new()
     }
+}
 
-    fn left(self: &Keyboard, layer: usize) -> Group {
-        Group::new()
-            .add(self.leftmain(layer))
-            .add(self.leftthumb(layer).set("transform", "translate(675,325)"))
-        // bottom right corner -- 975, 625
+impl Renderer for SvgRenderer {
+    type Output = String;
+
+    fn begin(&mut self, options: &RenderOptions) {
+        let (x, y, width, height) = options.geometry.bounds();
+        self.bounds = (x * UNIT, y * UNIT, width * UNIT, height * UNIT);
+        self.options = options.clone();
     }
 
-    fn right(self: &Keyboard, layer: usize) -> Group {
-        Group::new()
-            .add(self.rightmain(layer).set("transform","translate(250,0)"))
-            .add(self.rightthumb(layer).set("transform", "translate(0,325)"))
-            .set("transform","translate(1000,0)")
+    fn begin_layer(&mut self, layer: usize) {
+        self.accented.insert(layer);
+        self.current = Group::new();
     }
 
-    fn layer(self: &Keyboard, layer: usize) -> Group {
-        Group::new()
-            .add(self.left(layer))
-            .add(self.right(layer))
-            .set("id", format!("layer{}", layer))
-            .set("visibility", "hidden")
+    fn key(&mut self, key: &KeyView) {
+        self.categories.insert(key.category);
+        if let Some(layer) = key.target {
+            self.accented.insert(layer as usize);
+        }
+        let keynode = self.keynode(key);
+        let current = ::std::mem::replace(&mut self.current, Group::new());
+        self.current = current.add(keynode);
     }
 
-    fn keymap(self: &Keyboard) -> Group {
-        Range{start: 0, end: self.keymaps.len()}
-            .fold(Group::new(),
-                  |grp, i| grp.add(self.layer(i)))
+    fn end_layer(&mut self, layer: usize) {
+        let current = ::std::mem::replace(&mut self.current, Group::new())
+            .set("id", format!("layer{}", layer));
+
+        let group = if self.options.script {
+            current.set("visibility", "hidden")
+        } else {
+            let offset = self.layers.len() as f64 * (self.bounds.3 + LAYER_GAP);
+            current.set("transform", format!("translate(0,{})", offset))
+        };
+        self.layers.push(group);
     }
 
-    pub fn svg(self: &Keyboard) -> String {
+    fn finish(self) -> String {
 
         let cdata = |s: &str| format!("<![CDATA[{}]]>",s);
 
-        let css = include_str!("data/keyboard.css");
+        let theme = &self.options.theme;
 
-        let style = Style::new(cdata(css));
+        let mut css = format!("{}\ntext {{\n    fill: {};\n}}\n\n.shifted {{\n    fill: {};\n}}\n",
+                              include_str!("data/keyboard.css"), theme.text, theme.shifted_text);
+        for (category, fill) in &theme.categories {
+            css.push_str(&format!("\n.{} .inside {{\n    fill: {};\n}}\n", category.name(), fill));
+        }
+        // Each layer is outlined in its accent, and keys that lead to a
+        // layer are tinted with it
+        for (layer, accent) in self.accented.iter().filter_map(|&l| theme.accent(l).map(|a| (l, a))) {
+            css.push_str(&format!("\n#layer{0} .outside {{\n    stroke: {1};\n}}\n\n\
+                                   .to-layer{0} .inside {{\n    fill: {1};\n    fill-opacity: 0.6;\n}}\n",
+                                  layer, accent));
+        }
 
-        let js = include_str!("data/keyboard.js");
+        let style = Style::new(cdata(&css));
 
-        let code = Script::new(cdata(js));
+        let (x, y, width, layer_height) = self.bounds;
+        let layers = self.layers.len().max(1) as f64;
+        let keys_height = if self.options.script {
+            layer_height
+        } else {
+            layers * layer_height + (layers - 1.0) * LAYER_GAP
+        };
+        let height = keys_height + self.legend_height();
 
-        let keyboard = self.keymap();
+        let mut doc = Document::new()
+            .set("viewBox", (x, y, width, height))
+            .add(style);
 
-        let keyoutside = LinearGradient::new()
-            .set("id", "keyoutside")
-            .set("x1", "0%")
-            .set("x2", "0%")
-            .set("y1", "0%")
-            .set("y2", "100%")
-            .add(Stop::new()
-                 .set("offset", "0%")
-                 .set("stop-color", "#E1E1E1"))
-            .add(Stop::new()
-                 .set("offset", "100%")
-                 .set("stop-color", "#B2B2B2"));
+        if let Some(ref size) = self.options.key_size {
+            doc = doc
+                .set("width", format!("{}{}", width / UNIT * size.length, size.unit))
+                .set("height", format!("{}{}", height / UNIT * size.length, size.unit));
+        }
 
-        let keyinside = LinearGradient::new()
-            .set("id", "keyinside")
-            .set("x1", "0%")
-            .set("x2", "100%")
-            .set("y1", "0%")
-            .set("y2", "0%")
-            .add(Stop::new()
-                 .set("offset", "0%")
-                 .set("stop-color", "#D6D6D6"))
-            .add(Stop::new()
-                 .set("offset", "50%")
-                 .set("stop-color", "#EBEBEB"))
-            .add(Stop::new()
-                 .set("offset", "100%")
-                 .set("stop-color", "#D6D6D6"));
+        if self.options.script {
+            let js = include_str!("data/keyboard.js");
+            doc = doc.add(Script::new(cdata(js)));
+        }
 
-        let defs = Definitions::new()
-            .add(keyoutside)
-            .add(keyinside);
+        doc = doc.add(self.defs());
+
+        if let Some(ref background) = theme.background {
+            doc = doc.add(Rectangle::new()
+                          .set("x", x)
+                          .set("y", y)
+                          .set("width", width)
+                          .set("height", height)
+                          .set("fill", background.as_str()));
+        }
+
+        let legend = self.legend(y + keys_height + LAYER_GAP);
+
+        let keyboard = self.layers.into_iter()
+            .fold(Group::new(), |grp, layer| grp.add(layer));
 
-        let doc = Document::new()
-            .set("viewBox", (0,0,2000,625))
-            .add(style)
-            .add(code)
-            .add(defs)
-            .add(keyboard);
+        doc.add(keyboard).add(legend).to_string()
+    }
+}
 
-        doc.to_string()
+impl Keyboard {
+    pub fn svg(&self) -> String {
+        self.render(SvgRenderer::new())
     }
 }
@@ -0,0 +1,367 @@
+use options::Os;
+use category::Category;
+use category::Category::*;
+
+/// A keycode with its legends. A key may go by several names, short
+/// one first, such as BSPC and BSPACE.
+#[derive(Debug, PartialEq)]
+pub struct Keycode {
+    pub names: &'static [&'static str],
+    pub normal: &'static str,
+    pub shifted: &'static str,
+    // For QMK's shifted keycodes such as PIPE, the key that is shifted
+    pub shift_of: Option<&'static str>,
+    pub category: Category
+}
+
+impl Keycode {
+    pub fn name(&self) -> &'static str {
+        self.names[0]
+    }
+}
+
+const fn key(names: &'static [&'static str], normal: &'static str, shifted: &'static str,
+             category: Category) -> Keycode {
+    Keycode{ names, normal, shifted, shift_of: None, category }
+}
+
+const fn shift(names: &'static [&'static str], legend: &'static str, of: &'static str) -> Keycode {
+    Keycode{ names, normal: legend, shifted: "", shift_of: Some(of), category: Symbol }
+}
+
+// The basic keycodes come first, in HID usage order, then the system,
+// media and mouse keys, then the shifted keycodes.
+const KEYCODES: &[Keycode] = &[
+    key(&["NO"], "", "", Other),
+    key(&["TRNS", "TRANSPARENT"], "▽", "", Transparent),
+    key(&["A"], "A", "", Alpha), key(&["B"], "B", "", Alpha), key(&["C"], "C", "", Alpha),
+    key(&["D"], "D", "", Alpha), key(&["E"], "E", "", Alpha), key(&["F"], "F", "", Alpha),
+    key(&["G"], "G", "", Alpha), key(&["H"], "H", "", Alpha), key(&["I"], "I", "", Alpha),
+    key(&["J"], "J", "", Alpha), key(&["K"], "K", "", Alpha), key(&["L"], "L", "", Alpha),
+    key(&["M"], "M", "", Alpha), key(&["N"], "N", "", Alpha), key(&["O"], "O", "", Alpha),
+    key(&["P"], "P", "", Alpha), key(&["Q"], "Q", "", Alpha), key(&["R"], "R", "", Alpha),
+    key(&["S"], "S", "", Alpha), key(&["T"], "T", "", Alpha), key(&["U"], "U", "", Alpha),
+    key(&["V"], "V", "", Alpha), key(&["W"], "W", "", Alpha), key(&["X"], "X", "", Alpha),
+    key(&["Y"], "Y", "", Alpha), key(&["Z"], "Z", "", Alpha),
+    key(&["1"], "1", "!", Number), key(&["2"], "2", "@", Number), key(&["3"], "3", "#", Number),
+    key(&["4"], "4", "$", Number), key(&["5"], "5", "%", Number), key(&["6"], "6", "^", Number),
+    key(&["7"], "7", "&", Number), key(&["8"], "8", "*", Number), key(&["9"], "9", "(", Number),
+    key(&["0"], "0", ")", Number),
+    key(&["ENT", "ENTER"], "⏎", "", Other),
+    key(&["ESC", "ESCAPE"], "Esc", "", Other),
+    key(&["BSPC", "BSPACE"], "⌫", "", Other),
+    key(&["TAB"], "⇥", "", Other),
+    key(&["SPC", "SPACE"], "␣", "", Other),
+    key(&["MINS", "MINUS"], "-", "_", Symbol),
+    key(&["EQL", "EQUAL"], "=", "+", Symbol),
+    key(&["LBRC", "LBRACKET"], "[", "{", Symbol),
+    key(&["RBRC", "RBRACKET"], "]", "}", Symbol),
+    key(&["BSLS", "BSLASH"], "\\", "|", Symbol),
+    key(&["NUHS", "NONUS_HASH"], "#", "~", Symbol),
+    key(&["SCLN", "SCOLON"], ";", ":", Symbol),
+    key(&["QUOT", "QUOTE"], "'", "\"", Symbol),
+    key(&["GRV", "GRAVE", "ZKHK"], "`", "~", Symbol),
+    key(&["COMM", "COMMA"], ",", "<", Symbol),
+    key(&["DOT"], ".", ">", Symbol),
+    key(&["SLSH", "SLASH"], "/", "?", Symbol),
+    key(&["CAPS", "CAPSLOCK", "CLCK"], "⇪", "", Other),
+    key(&["F1"], "F1", "", Function), key(&["F2"], "F2", "", Function), key(&["F3"], "F3", "", Function),
+    key(&["F4"], "F4", "", Function), key(&["F5"], "F5", "", Function), key(&["F6"], "F6", "", Function),
+    key(&["F7"], "F7", "", Function), key(&["F8"], "F8", "", Function), key(&["F9"], "F9", "", Function),
+    key(&["F10"], "F10", "", Function), key(&["F11"], "F11", "", Function), key(&["F12"], "F12", "", Function),
+    key(&["PSCR", "PSCREEN"], "⎙", "", Other),
+    key(&["SLCK", "SCROLLLOCK"], "ScrLk", "", Navigation),
+    key(&["PAUS", "PAUSE", "BRK"], "Pause", "", Navigation),
+    key(&["INS", "INSERT"], "Ins", "", Navigation),
+    key(&["HOME"], "⇱", "", Navigation),
+    key(&["PGUP"], "⇞", "", Navigation),
+    key(&["DEL", "DELETE"], "⌦", "", Navigation),
+    key(&["END"], "⇲", "", Navigation),
+    key(&["PGDN", "PGDOWN"], "⇟", "", Navigation),
+    key(&["RGHT", "RIGHT"], "→", "", Navigation),
+    key(&["LEFT"], "←", "", Navigation),
+    key(&["DOWN"], "↓", "", Navigation),
+    key(&["UP"], "↑", "", Navigation),
+    key(&["NLCK", "NUMLOCK"], "NumLk", "", Other),
+    key(&["PSLS", "KP_SLASH"], "/", "", Symbol),
+    key(&["PAST", "KP_ASTERISK"], "*", "", Symbol),
+    key(&["PMNS", "KP_MINUS"], "-", "", Symbol),
+    key(&["PPLS", "KP_PLUS"], "+", "", Symbol),
+    key(&["PENT", "KP_ENTER"], "⏎", "", Other),
+    key(&["P1", "KP_1"], "1", "", Number), key(&["P2", "KP_2"], "2", "", Number),
+    key(&["P3", "KP_3"], "3", "", Number), key(&["P4", "KP_4"], "4", "", Number),
+    key(&["P5", "KP_5"], "5", "", Number), key(&["P6", "KP_6"], "6", "", Number),
+    key(&["P7", "KP_7"], "7", "", Number), key(&["P8", "KP_8"], "8", "", Number),
+    key(&["P9", "KP_9"], "9", "", Number), key(&["P0", "KP_0"], "0", "", Number),
+    key(&["PDOT", "KP_DOT"], ".", "", Symbol),
+    key(&["NUBS", "NONUS_BSLASH"], "\\", "|", Symbol),
+    key(&["APP", "APPLICATION"], "☰", "", Other),
+    key(&["POWER"], "⏻", "", Other),
+    key(&["PEQL", "KP_EQUAL"], "=", "", Symbol),
+    key(&["F13"], "F13", "", Function), key(&["F14"], "F14", "", Function), key(&["F15"], "F15", "", Function),
+    key(&["F16"], "F16", "", Function), key(&["F17"], "F17", "", Function), key(&["F18"], "F18", "", Function),
+    key(&["F19"], "F19", "", Function), key(&["F20"], "F20", "", Function), key(&["F21"], "F21", "", Function),
+    key(&["F22"], "F22", "", Function), key(&["F23"], "F23", "", Function), key(&["F24"], "F24", "", Function),
+    key(&["EXEC", "EXECUTE"], "Exec", "", Other),
+    key(&["HELP"], "Help", "", Other),
+    key(&["MENU"], "Menu", "", Other),
+    key(&["SLCT", "SELECT"], "Select", "", Other),
+    key(&["STOP"], "Stop", "", Other),
+    key(&["AGIN", "AGAIN"], "Again", "", Other),
+    key(&["UNDO"], "Undo", "", Other),
+    key(&["CUT"], "Cut", "", Other),
+    key(&["COPY"], "Copy", "", Other),
+    key(&["PSTE", "PASTE"], "Paste", "", Other),
+    key(&["FIND"], "Find", "", Other),
+    key(&["_MUTE"], "🔇", "", Media),
+    key(&["_VOLUP"], "🔊", "", Media),
+    key(&["_VOLDOWN"], "🔉", "", Media),
+    key(&["LCAP", "LOCKING_CAPS"], "⇪", "", Other),
+    key(&["LNUM", "LOCKING_NUM"], "NumLk", "", Other),
+    key(&["LSCR", "LOCKING_SCROLL"], "ScrLk", "", Other),
+    key(&["PCMM", "KP_COMMA"], ",", "", Symbol),
+    key(&["KP_EQUAL_AS400"], "=", "", Symbol),
+    key(&["RO", "INT1"], "ろ", "", Other),
+    key(&["KANA", "INT2"], "かな", "", Other),
+    key(&["JYEN", "INT3"], "¥", "", Other),
+    key(&["HENK", "INT4"], "変換", "", Other),
+    key(&["MHEN", "INT5"], "無変換", "", Other),
+    key(&["INT6"], "Int6", "", Other), key(&["INT7"], "Int7", "", Other),
+    key(&["INT8"], "Int8", "", Other), key(&["INT9"], "Int9", "", Other),
+    key(&["HAEN", "LANG1"], "한/영", "", Other),
+    key(&["HANJ", "LANG2"], "漢字", "", Other),
+    key(&["LANG3"], "Lang3", "", Other), key(&["LANG4"], "Lang4", "", Other),
+    key(&["LANG5"], "Lang5", "", Other), key(&["LANG6"], "Lang6", "", Other),
+    key(&["LANG7"], "Lang7", "", Other), key(&["LANG8"], "Lang8", "", Other),
+    key(&["LANG9"], "Lang9", "", Other),
+    key(&["ERAS", "ALT_ERASE"], "Erase", "", Other),
+    key(&["SYSREQ"], "SysRq", "", Other),
+    key(&["CANCEL"], "Cancel", "", Other),
+    key(&["CLR", "CLEAR"], "Clear", "", Other),
+    key(&["PRIOR"], "Prior", "", Other),
+    key(&["RETURN"], "⏎", "", Other),
+    key(&["SEPARATOR"], "Sep", "", Other),
+    key(&["OUT"], "Out", "", Other),
+    key(&["OPER"], "Oper", "", Other),
+    key(&["CLEAR_AGAIN"], "Clear", "", Other),
+    key(&["CRSEL"], "CrSel", "", Other),
+    key(&["EXSEL"], "ExSel", "", Other),
+    key(&["LCTL", "LCTRL"], "Ctrl", "", Modifier),
+    key(&["LSFT", "LSHIFT"], "Shift", "", Modifier),
+    key(&["LALT", "LOPT"], "Alt", "", Modifier),
+    key(&["LGUI", "LCMD", "LWIN"], "Gui", "", Modifier),
+    key(&["RCTL", "RCTRL"], "Ctrl", "", Modifier),
+    key(&["RSFT", "RSHIFT"], "Shift", "", Modifier),
+    key(&["RALT", "ROPT", "ALGR"], "AltGr", "", Modifier),
+    key(&["RGUI", "RCMD", "RWIN"], "Gui", "", Modifier),
+
+    key(&["PWR", "SYSTEM_POWER"], "⏻", "", Media),
+    key(&["SLEP", "SYSTEM_SLEEP"], "Sleep", "", Media),
+    key(&["WAKE", "SYSTEM_WAKE"], "Wake", "", Media),
+    key(&["MUTE", "AUDIO_MUTE"], "🔇", "", Media),
+    key(&["VOLU", "AUDIO_VOL_UP"], "🔊", "", Media),
+    key(&["VOLD", "AUDIO_VOL_DOWN"], "🔉", "", Media),
+    key(&["MNXT", "MEDIA_NEXT_TRACK"], "⏭", "", Media),
+    key(&["MPRV", "MEDIA_PREV_TRACK"], "⏮", "", Media),
+    key(&["MSTP", "MEDIA_STOP"], "⏹", "", Media),
+    key(&["MPLY", "MEDIA_PLAY_PAUSE"], "⏯", "", Media),
+    key(&["MSEL", "MEDIA_SELECT"], "Media", "", Media),
+    key(&["EJCT", "MEDIA_EJECT"], "⏏", "", Media),
+    key(&["MFFD", "MEDIA_FAST_FORWARD"], "⏩", "", Media),
+    key(&["MRWD", "MEDIA_REWIND"], "⏪", "", Media),
+    key(&["MAIL"], "✉", "", Media),
+    key(&["CALC", "CALCULATOR"], "Calc", "", Media),
+    key(&["MYCM", "MY_COMPUTER"], "💻", "", Media),
+    key(&["WSCH", "WWW_SEARCH"], "🔍", "", Media),
+    key(&["WHOM", "WWW_HOME"], "⌂", "", Media),
+    key(&["WBAK", "WWW_BACK"], "Back", "", Media),
+    key(&["WFWD", "WWW_FORWARD"], "Fwd", "", Media),
+    key(&["WSTP", "WWW_STOP"], "Stop", "", Media),
+    key(&["WREF", "WWW_REFRESH"], "⟳", "", Media),
+    key(&["WFAV", "WWW_FAVORITES"], "★", "", Media),
+    key(&["BRIU", "BRIGHTNESS_UP"], "🔆", "", Media),
+    key(&["BRID", "BRIGHTNESS_DOWN"], "🔅", "", Media),
+
+    key(&["MS_U", "MS_UP"], "M↑", "", Mouse),
+    key(&["MS_D", "MS_DOWN"], "M↓", "", Mouse),
+    key(&["MS_L", "MS_LEFT"], "M←", "", Mouse),
+    key(&["MS_R", "MS_RIGHT"], "M→", "", Mouse),
+    key(&["BTN1", "MS_BTN1"], "M1", "", Mouse),
+    key(&["BTN2", "MS_BTN2"], "M2", "", Mouse),
+    key(&["BTN3", "MS_BTN3"], "M3", "", Mouse),
+    key(&["BTN4", "MS_BTN4"], "M4", "", Mouse),
+    key(&["BTN5", "MS_BTN5"], "M5", "", Mouse),
+    key(&["WH_U", "MS_WH_UP"], "Wh↑", "", Mouse),
+    key(&["WH_D", "MS_WH_DOWN"], "Wh↓", "", Mouse),
+    key(&["WH_L", "MS_WH_LEFT"], "Wh←", "", Mouse),
+    key(&["WH_R", "MS_WH_RIGHT"], "Wh→", "", Mouse),
+    key(&["ACL0", "MS_ACCEL0"], "Acc0", "", Mouse),
+    key(&["ACL1", "MS_ACCEL1"], "Acc1", "", Mouse),
+    key(&["ACL2", "MS_ACCEL2"], "Acc2", "", Mouse),
+
+    shift(&["TILD", "TILDE"], "~", "GRV"),
+    shift(&["EXLM", "EXCLAIM"], "!", "1"),
+    shift(&["AT"], "@", "2"),
+    shift(&["HASH"], "#", "3"),
+    shift(&["DLR", "DOLLAR"], "$", "4"),
+    shift(&["PERC", "PERCENT"], "%", "5"),
+    shift(&["CIRC", "CIRCUMFLEX"], "^", "6"),
+    shift(&["AMPR", "AMPERSAND"], "&", "7"),
+    shift(&["ASTR", "ASTERISK"], "*", "8"),
+    shift(&["LPRN", "LEFT_PAREN"], "(", "9"),
+    shift(&["RPRN", "RIGHT_PAREN"], ")", "0"),
+    shift(&["UNDS", "UNDERSCORE"], "_", "MINS"),
+    shift(&["PLUS"], "+", "EQL"),
+    shift(&["LCBR", "LEFT_CURLY_BRACE"], "{", "LBRC"),
+    shift(&["RCBR", "RIGHT_CURLY_BRACE"], "}", "RBRC"),
+    shift(&["PIPE"], "|", "BSLS"),
+    shift(&["COLN", "COLON"], ":", "SCLN"),
+    shift(&["DQUO", "DQT", "DOUBLE_QUOTE"], "\"", "QUOT"),
+    shift(&["LABK", "LT", "LEFT_ANGLE_BRACKET"], "<", "COMM"),
+    shift(&["RABK", "GT", "RIGHT_ANGLE_BRACKET"], ">", "DOT"),
+    shift(&["QUES", "QUESTION"], "?", "SLSH")
+];
+
+pub fn all() -> &'static [Keycode] {
+    KEYCODES
+}
+
+/// Find a keycode by any of its names, with or without a KC_ or MOD_
+/// prefix
+pub fn lookup(name: &str) -> Option<&'static Keycode> {
+    let name = name.trim_start_matches("KC_").trim_start_matches("MOD_");
+    KEYCODES.iter().find(|k| k.names.contains(&name))
+}
+
+/// The short name of a keycode, such as BSPC for KC_BSPACE. Names that
+/// are not known keycodes lose their KC_ or MOD_ prefix.
+pub fn canonical(name: &str) -> String {
+    match lookup(name) {
+        Some(keycode) => String::from(keycode.name()),
+        None => String::from(name.trim_start_matches("KC_").trim_start_matches("MOD_"))
+    }
+}
+
+/// The keycode sent by a key on the computer's own keyboard, from the
+/// `code` of its DOM KeyboardEvent such as KeyA, Digit1 or ShiftLeft
+pub fn from_dom_code(code: &str) -> Option<&'static Keycode> {
+    if code.len() == 4 && code.starts_with("Key") || code.starts_with("Digit") {
+        return lookup(code.trim_start_matches("Key").trim_start_matches("Digit"));
+    }
+    if code.starts_with('F') && code[1..].parse::<u32>().is_ok() {
+        return lookup(code);
+    }
+    if code.starts_with("Numpad") && code[6..].parse::<u32>().is_ok() {
+        return lookup(&format!("P{}", &code[6..]));
+    }
+    let name = match code {
+        "Enter" => "ENT", "Escape" => "ESC", "Backspace" => "BSPC", "Tab" => "TAB",
+        "Space" => "SPC", "Minus" => "MINS", "Equal" => "EQL",
+        "BracketLeft" => "LBRC", "BracketRight" => "RBRC", "Backslash" => "BSLS",
+        "Semicolon" => "SCLN", "Quote" => "QUOT", "Backquote" => "GRV",
+        "Comma" => "COMM", "Period" => "DOT", "Slash" => "SLSH",
+        "IntlBackslash" => "NUBS", "IntlRo" => "RO", "IntlYen" => "JYEN",
+        "CapsLock" => "CAPS", "PrintScreen" => "PSCR", "ScrollLock" => "SLCK",
+        "Pause" => "PAUS", "Insert" => "INS", "Home" => "HOME", "PageUp" => "PGUP",
+        "Delete" => "DEL", "End" => "END", "PageDown" => "PGDN",
+        "ArrowRight" => "RGHT", "ArrowLeft" => "LEFT", "ArrowDown" => "DOWN", "ArrowUp" => "UP",
+        "NumLock" => "NLCK", "NumpadDivide" => "PSLS", "NumpadMultiply" => "PAST",
+        "NumpadSubtract" => "PMNS", "NumpadAdd" => "PPLS", "NumpadEnter" => "PENT",
+        "NumpadDecimal" => "PDOT", "NumpadEqual" => "PEQL", "ContextMenu" => "APP",
+        "ControlLeft" => "LCTL", "ShiftLeft" => "LSFT", "AltLeft" => "LALT", "MetaLeft" => "LGUI",
+        "ControlRight" => "RCTL", "ShiftRight" => "RSFT", "AltRight" => "RALT", "MetaRight" => "RGUI",
+        _ => return None
+    };
+    lookup(name)
+}
+
+/// Normal and shifted legends for a keycode. Unknown keycodes are shown
+/// by name.
+pub fn legends(name: &str) -> (String, String) {
+    match lookup(name) {
+        Some(keycode) => (String::from(keycode.normal), String::from(keycode.shifted)),
+        None => (name.replace("KC_", "").replace("MOD_", ""), String::new())
+    }
+}
+
+/// What a modifier keycode is called on an operating system
+pub fn modifier(name: &str, os: Os) -> Option<&'static str> {
+    let legend = match (lookup(name)?.name(), os) {
+        ("LCTL", Os::Mac) | ("RCTL", Os::Mac) => "⌃",
+        ("LSFT", Os::Mac) | ("RSFT", Os::Mac) => "⇧",
+        ("LALT", Os::Mac) | ("RALT", Os::Mac) => "⌥",
+        ("LGUI", Os::Mac) | ("RGUI", Os::Mac) => "⌘",
+        ("LGUI", Os::Windows) | ("RGUI", Os::Windows) => "⊞ Win",
+        ("LGUI", Os::Linux) | ("RGUI", Os::Linux) => "◆ Super",
+        ("LCTL", _) | ("RCTL", _) => "Ctrl",
+        ("LSFT", _) | ("RSFT", _) => "Shift",
+        ("LALT", _) => "Alt",
+        ("RALT", _) => "AltGr",
+        _ => return None
+    };
+    Some(legend)
+}
+
+/// Legends for a keycode, with modifiers named for `os` when given
+pub fn legends_for(name: &str, os: Option<Os>) -> (String, String) {
+    match os.and_then(|os| modifier(name, os)) {
+        Some(legend) => (String::from(legend), String::new()),
+        None => legends(name)
+    }
+}
+
+/// One legend for modifiers held with a key, such as ⌃X or Ctrl+X.
+/// Modifiers may be combined with `|`.
+pub fn chord(mods: &str, key: &str, os: Option<Os>) -> String {
+    let mods: Vec<String> = mods.split('|')
+        .map(|m| legends_for(m.trim(), os).0)
+        .collect();
+    match os {
+        Some(Os::Mac) => format!("{}{}", mods.concat(), key),
+        _ => format!("{}+{}", mods.join("+"), key)
+    }
+}
+
+#[test]
+fn test_os_modifiers() {
+    assert_eq!(legends_for("KC_LGUI", Some(Os::Mac)).0, "⌘");
+    assert_eq!(legends_for("KC_LGUI", Some(Os::Windows)).0, "⊞ Win");
+    assert_eq!(legends_for("MOD_RGUI", Some(Os::Linux)).0, "◆ Super");
+    assert_eq!(legends_for("KC_LALT", Some(Os::Mac)).0, "⌥");
+    assert_eq!(legends_for("KC_LALT", None).0, "Alt");
+    assert_eq!(legends_for("KC_A", Some(Os::Mac)).0, "A");
+
+    assert_eq!(chord("MOD_LCTL", "X", Some(Os::Mac)), "⌃X");
+    assert_eq!(chord("MOD_LCTL | MOD_LSFT", "X", Some(Os::Mac)), "⌃⇧X");
+    assert_eq!(chord("MOD_LCTL | MOD_LSFT", "X", Some(Os::Linux)), "Ctrl+Shift+X");
+    assert_eq!(chord("MOD_LGUI", "X", None), "Gui+X");
+}
+
+#[test]
+fn test_keycode_aliases() {
+    assert_eq!(lookup("KC_BSPC"), lookup("BSPACE"));
+    assert_eq!(lookup("KC_BSPC").map(Keycode::name), Some("BSPC"));
+    assert_eq!(legends("KC_VOLU"), (String::from("🔊"), String::new()));
+    assert_eq!(legends("AUDIO_VOL_UP"), legends("VOLU"));
+    assert_eq!(legends("KC_1"), (String::from("1"), String::from("!")));
+    assert_eq!(legends("MOD_LSFT"), (String::from("Shift"), String::new()));
+    assert_eq!(legends("KC_NOPE"), (String::from("NOPE"), String::new()));
+    assert_eq!(lookup("KC_PIPE").and_then(|k| k.shift_of), Some("BSLS"));
+    assert_eq!(canonical("KC_BSPACE"), "BSPC");
+    assert_eq!(canonical("MOD_MY_KEY"), "MY_KEY");
+
+    let dom = |code| from_dom_code(code).map(Keycode::name);
+    assert_eq!(dom("KeyQ"), Some("Q"));
+    assert_eq!(dom("Digit7"), Some("7"));
+    assert_eq!(dom("F11"), Some("F11"));
+    assert_eq!(dom("Numpad4"), Some("P4"));
+    assert_eq!(dom("ShiftRight"), Some("RSFT"));
+    assert_eq!(dom("Keyboard"), None);
+
+    // No name is used twice
+    let mut names: Vec<&str> = all().iter().flat_map(|k| k.names.iter().cloned()).collect();
+    let count = names.len();
+    names.sort();
+    names.dedup();
+    assert_eq!(names.len(), count);
+}
@@ -0,0 +1,171 @@
+use serde_json::Value;
+
+use render::*;
+use options::*;
+use theme::Theme;
+use legend::*;
+
+// KLE's own keycap colour, for keys without a category fill
+const DEFAULT_COLOUR: &str = "#cccccc";
+
+// Vertical space between stacked layers, in key units
+const LAYER_GAP: f64 = 0.5;
+
+/// Keyboard-layout-editor raw data with every layer stacked
+/// vertically, each headed by a decal naming the layer.
+pub struct KleRenderer {
+    origin: (f64, f64),
+    height: f64,
+    top: f64,
+    // Layers begun so far, which sets where the next one goes
+    drawn: usize,
+    theme: Theme,
+    rows: Vec<Value>
+}
+
+impl KleRenderer {
+    pub fn new() -> KleRenderer {
+        KleRenderer{
+            origin: (0.0, 0.0),
+            height: 0.0,
+            top: 0.0,
+            drawn: 0,
+            theme: Theme::default(),
+            rows: vec![json!({"name": "ergowhat"})]
+        }
+    }
+
+    // Layer keys take the accent of their layer, others their category
+    fn colour(&self, key: &KeyView) -> &str {
+        let accent = key.target.and_then(|layer| self.theme.accent(layer as usize));
+        let fill = self.theme.categories.get(&key.category).map(String::as_str);
+        accent.or(fill).unwrap_or(DEFAULT_COLOUR)
+    }
+
+    // Legends in KLE label order for the default alignment: top-left,
+    // bottom-left, top-right, bottom-right, then the front legends, the
+    // middle row and the centres of the top, middle and bottom rows.
+    // Shifted keys keep the usual stacked pair in the left column.
+    fn labels(legends: &[Legend]) -> String {
+        let stacked = legends.iter().any(|l| l.slot == Slot::Shifted);
+        let mut labels = vec![String::new(); 12];
+        for legend in legends {
+            let index = match legend.slot {
+                Slot::Shifted => 0,
+                // Moved to the middle left when the shifted legend is there
+                Slot::TopLeft if stacked => 6,
+                Slot::TopLeft => 0,
+                Slot::Tap if stacked => 1,
+                Slot::Tap => 9,
+                Slot::LayerHint => 2,
+                Slot::BottomRight => 3,
+                Slot::Hold => 10
+            };
+            labels[index] = legend.text.clone();
+        }
+
+        while labels.last().is_some_and(String::is_empty) {
+            labels.pop();
+        }
+        labels.join("\n")
+    }
+}
+
+impl Default for KleRenderer {
+    fn default() -> KleRenderer {
+        KleRenderer::new()
+    }
+}
+
+impl Renderer for KleRenderer {
+    type Output = String;
+
+    fn begin(&mut self, options: &RenderOptions) {
+        let (min_x, min_y, _, height) = options.geometry.bounds();
+        self.origin = (min_x, min_y);
+        self.height = height;
+        self.theme = options.theme.clone();
+    }
+
+    fn begin_layer(&mut self, layer: usize) {
+        self.top = self.drawn as f64 * (self.height + 1.0 + LAYER_GAP);
+        self.drawn += 1;
+        self.rows.push(json!([{"r": 0, "rx": 0, "ry": self.top, "d": true, "w": 3},
+                              format!("Layer {}", layer)]));
+    }
+
+    fn key(&mut self, key: &KeyView) {
+        let pos = key.position;
+        let (min_x, min_y) = self.origin;
+
+        // Giving every key its own rotation origin keeps the positions
+        // absolute, whatever the row structure
+        let rx = pos.rx - min_x;
+        let ry = pos.ry - min_y + self.top + 1.0;
+        let mut props = json!({
+            "r": pos.r,
+            "rx": rx,
+            "ry": ry,
+            "x": pos.x - min_x - rx,
+            "y": pos.y - min_y + self.top + 1.0 - ry,
+            // Colours carry over to the following keys in KLE, so every
+            // key sets its own
+            "c": self.colour(key)
+        });
+        if pos.w != 1.0 {
+            props["w"] = json!(pos.w);
+        }
+        if pos.h != 1.0 {
+            props["h"] = json!(pos.h);
+        }
+
+        let labels = KleRenderer::labels(&key.legends);
+        self.rows.push(Value::Array(vec![props, Value::String(labels)]));
+    }
+
+    fn finish(self) -> String {
+        Value::Array(self.rows).to_string()
+    }
+}
+
+impl Keyboard {
+    pub fn kle(&self) -> String {
+        self.render(KleRenderer::new())
+    }
+}
+
+#[test]
+fn test_kle_roundtrip() {
+    use types::*;
+    use geometry::*;
+
+    let keymap: KeyMap = (0..76).map(|_| Key::Key(String::from("KC_COMM"))).collect();
+    let keyboard = Keyboard::new(vec![keymap], ActionMap::new());
+    let kle = keyboard.kle();
+    assert!(kle.contains(r#""<\n,""#));
+
+    let imported = Geometry::from_kle(&kle).unwrap();
+    let original = Geometry::ergodox();
+    assert_eq!(imported.keys.len(), original.keys.len());
+    for (a, b) in imported.keys.iter().zip(original.keys.iter()) {
+        let close = |m: f64, n: f64| (m - n).abs() < 1e-9;
+        assert!(close(a.x, b.x) && close(a.y - 1.0, b.y), "{:?} != {:?}", a, b);
+        assert!(close(a.rx, b.rx) && close(a.ry - 1.0, b.ry), "{:?} != {:?}", a, b);
+        assert_eq!((a.w, a.h, a.r), (b.w, b.h, b.r));
+    }
+}
+
+#[test]
+fn test_kle_one_layer() {
+    use types::*;
+
+    let keymaps: KeyMapVec = (0..2)
+        .map(|_| (0..76).map(|_| Key::Key(String::from("KC_A"))).collect())
+        .collect();
+    let options = RenderOptions{ layers: vec![1], ..RenderOptions::default() };
+    let keyboard = Keyboard::with_options(keymaps, ActionMap::new(), options);
+    let kle: Value = ::serde_json::from_str(&keyboard.kle()).unwrap();
+
+    // The only layer drawn starts at the top
+    assert_eq!(kle[1], json!([{"r": 0, "rx": 0, "ry": 0.0, "d": true, "w": 3}, "Layer 1"]));
+}
@@ -0,0 +1,153 @@
+use std::collections::HashMap;
+use toml;
+
+use types::*;
+
+/// Legends chosen by the user in place of the built in ones. A key
+/// shows the label for its layer and position first, then the label
+/// for its action, then the label for its keycode.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LabelMap {
+    keys: HashMap<String, String>,
+    actions: HashMap<String, String>,
+    positions: HashMap<(usize, usize), String>
+}
+
+// Keycodes and actions match however they are spaced and whether or not
+// their KC_ and MOD_ prefixes are written
+fn normalise(name: &str) -> String {
+    name.replace("KC_", "")
+        .replace("MOD_", "")
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect()
+}
+
+// The ways an action may be written: as its TMK macro, and as the QMK
+// shorthand where there is one
+fn action_names(action: &Action) -> Vec<String> {
+    let shorthand = match action {
+        Action::ModsKey(m, k) => Some(format!("{}({})", m, k)),
+        Action::ModsTapKey(m, k) => Some(format!("MT({},{})", m, k)),
+        Action::LayerMomentary(l) => Some(format!("MO({})", l)),
+        Action::LayerToggle(l) => Some(format!("TG({})", l)),
+        Action::LayerTapToggle(l) => Some(format!("TT({})", l)),
+        Action::LayerSetClear(l) => Some(format!("TO({})", l)),
+        Action::DefaultLayerSet(l) => Some(format!("DF({})", l)),
+        Action::LayerTapKey(l, k) => Some(format!("LT({},{})", l, k)),
+        _ => None
+    };
+    Some(action.to_string()).into_iter()
+        .chain(shorthand)
+        .map(|name| normalise(&name))
+        .collect()
+}
+
+impl LabelMap {
+    pub fn new() -> LabelMap {
+        LabelMap::default()
+    }
+
+    /// Read labels from TOML with `[keys]`, `[actions]` and
+    /// `[layers.N]` tables. Actions are written as their TMK macro, a
+    /// QMK shorthand such as `LCTL(KC_X)`, or the FN key that runs them;
+    /// layer tables are keyed by position.
+    pub fn from_toml(input: &str) -> Result<LabelMap, String> {
+        let file: LabelFile = toml::from_str(input).map_err(|e| e.to_string())?;
+
+        let mut labels = LabelMap::new();
+        for (key, text) in file.keys.unwrap_or_default() {
+            labels.insert_key(&key, &text);
+        }
+        for (action, text) in file.actions.unwrap_or_default() {
+            labels.insert_action(&action, &text);
+        }
+        for (layer, positions) in file.layers.unwrap_or_default() {
+            let layer = layer.parse().map_err(|_| format!("Invalid layer: {}", layer))?;
+            for (index, text) in positions {
+                let index = index.parse().map_err(|_| format!("Invalid position: {}", index))?;
+                labels.insert_position(layer, index, &text);
+            }
+        }
+        Ok(labels)
+    }
+
+    pub fn insert_key(&mut self, key: &str, text: &str) {
+        self.keys.insert(normalise(key), String::from(text));
+    }
+
+    pub fn insert_action(&mut self, action: &str, text: &str) {
+        self.actions.insert(normalise(action), String::from(text));
+    }
+
+    pub fn insert_position(&mut self, layer: usize, index: usize, text: &str) {
+        self.positions.insert((layer, index), String::from(text));
+    }
+
+    /// Add the labels from `other`, replacing any for the same key
+    pub fn extend(&mut self, other: LabelMap) {
+        self.keys.extend(other.keys);
+        self.actions.extend(other.actions);
+        self.positions.extend(other.positions);
+    }
+
+    pub fn key(&self, name: &str) -> Option<&str> {
+        self.keys.get(&normalise(name)).map(String::as_str)
+    }
+
+    /// The label for the action run by the FN key `key`
+    pub fn action(&self, key: &Key, action: &Action) -> Option<&str> {
+        Some(key.to_string()).into_iter()
+            .chain(action_names(action))
+            .filter_map(|name| self.actions.get(&name))
+            .map(String::as_str)
+            .next()
+    }
+
+    pub fn position(&self, layer: usize, index: usize) -> Option<&str> {
+        self.positions.get(&(layer, index)).map(String::as_str)
+    }
+}
+
+// Labels as written in TOML
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct LabelFile {
+    keys: Option<HashMap<String, String>>,
+    actions: Option<HashMap<String, String>>,
+    layers: Option<HashMap<String, HashMap<String, String>>>
+}
+
+#[test]
+fn test_labels_toml() {
+    let labels = LabelMap::from_toml(r#"
+        [keys]
+        KC_MUTE = "Hush"
+        MY_KEY = "Mine"
+
+        [actions]
+        "LCTL(KC_X)" = "Cut"
+        "ACTION_LAYER_MOMENTARY(1)" = "Sym"
+        FN7 = "Teensy"
+
+        [layers.1]
+        12 = "Copy"
+    "#).unwrap();
+
+    assert_eq!(labels.key("MUTE"), Some("Hush"));
+    assert_eq!(labels.key("KC_MY_KEY"), Some("Mine"));
+    assert_eq!(labels.key("KC_A"), None);
+
+    let cut = Action::ModsKey(Key::Key(String::from("MOD_LCTL")), Key::Key(String::from("KC_X")));
+    assert_eq!(labels.action(&Key::Fx(6), &cut), Some("Cut"));
+    assert_eq!(labels.action(&Key::Fx(1), &Action::LayerMomentary(1)), Some("Sym"));
+    assert_eq!(labels.action(&Key::Fx(7), &Action::Function(Key::Key(String::from("TEENSY_KEY")))),
+               Some("Teensy"));
+    assert_eq!(labels.action(&Key::Fx(2), &Action::LayerToggle(2)), None);
+
+    assert_eq!(labels.position(1, 12), Some("Copy"));
+    assert_eq!(labels.position(0, 12), None);
+
+    assert!(LabelMap::from_toml("[layers.base]\n1 = \"x\"").is_err());
+    assert!(LabelMap::from_toml("[kyes]\nA = \"x\"").is_err());
+}
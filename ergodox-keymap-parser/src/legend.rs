@@ -0,0 +1,144 @@
+use fit::*;
+
+/// Where a legend is printed on the keycap
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Slot {
+    // What the key sends when tapped
+    Tap,
+    // What the key does when held, such as a modifier
+    Hold,
+    // What the key sends with shift
+    Shifted,
+    // The layer the key switches to
+    LayerHint,
+    TopLeft,
+    BottomRight
+}
+
+impl Slot {
+    /// Lower case name, used as a CSS class
+    pub fn name(self) -> &'static str {
+        match self {
+            Slot::Tap => "tap",
+            Slot::Hold => "hold",
+            Slot::Shifted => "shifted",
+            Slot::LayerHint => "layer-hint",
+            Slot::TopLeft => "top-left",
+            Slot::BottomRight => "bottom-right"
+        }
+    }
+}
+
+/// Text printed in one slot of a key. `name` is the keycode or action
+/// the legend was made from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Legend {
+    pub name: String,
+    pub text: String,
+    pub slot: Slot
+}
+
+/// Which end of the text sits on the placement point
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Anchor {
+    Start,
+    Middle,
+    End
+}
+
+impl Anchor {
+    /// Value of the SVG text-anchor property
+    pub fn name(self) -> &'static str {
+        match self {
+            Anchor::Start => "start",
+            Anchor::Middle => "middle",
+            Anchor::End => "end"
+        }
+    }
+}
+
+/// Baseline position of the first line of a legend, relative to the
+/// top left corner of the key, and its text fitted to the key
+#[derive(Debug, Clone, PartialEq)]
+pub struct Placement {
+    pub x: f64,
+    pub y: f64,
+    pub size: f64,
+    pub anchor: Anchor,
+    pub lines: Vec<String>,
+    pub line_height: f64
+}
+
+// Font sizes on a 1u key; smaller keys scale them down
+const TAP_SIZE: f64 = 20.0;
+const SHIFTED_SIZE: f64 = 16.0;
+const SMALL_SIZE: f64 = 12.0;
+
+// Space between the corner legends and the edge of the keycap top
+const PADDING: f64 = 5.0;
+
+// Shortest side of the keycap top of a 1u key
+const INSIDE_1U: f64 = 80.0;
+
+/// The keycap top of a key `width` by `height` units across, as
+/// (x, y, width, height). Legends are kept inside it.
+pub fn inside(width: f64, height: f64) -> (f64, f64, f64, f64) {
+    (10.0, 7.0, width - 20.0, height - 20.0)
+}
+
+/// Position every legend of a key `width` by `height` units across
+/// and fit its text to the space it has. The tap legend moves down to
+/// make room when there is a shifted legend above it, and may wrap onto
+/// a second line when there is not.
+pub fn layout(legends: &[Legend], width: f64, height: f64) -> Vec<Placement> {
+    let (x, y, w, h) = inside(width, height);
+    let scale = (w.min(h) / INSIDE_1U).min(1.0);
+    let (tap, shifted, small) = (TAP_SIZE * scale, SHIFTED_SIZE * scale, SMALL_SIZE * scale);
+    let center = x + w / 2.0;
+    let shifted_line = y + h * 0.35;
+    let has_shifted = legends.iter().any(|l| l.slot == Slot::Shifted);
+
+    // Centred legends get the whole width, corner legends half of it
+    let full = w - 2.0 * PADDING;
+    let half = w / 2.0 - PADDING;
+
+    legends.iter().map(|legend| {
+        let (px, py, size, anchor, room, max_lines) = match legend.slot {
+            Slot::Tap if has_shifted => (center, shifted_line + tap + 4.0 * scale, tap, Anchor::Middle, full, 1),
+            Slot::Tap => (center, y + h / 2.0 + tap * 0.35, tap, Anchor::Middle, full, 2),
+            Slot::Shifted => (center, shifted_line, shifted, Anchor::Middle, full, 1),
+            Slot::TopLeft => (x + PADDING, y + PADDING + small, small, Anchor::Start, half, 1),
+            Slot::LayerHint => (x + w - PADDING, y + PADDING + small, small, Anchor::End, half, 1),
+            Slot::Hold => (x + PADDING, y + h - PADDING, small, Anchor::Start, half, 1),
+            Slot::BottomRight => (x + w - PADDING, y + h - PADDING, small, Anchor::End, half, 1)
+        };
+        let fitted = fit(&legend.text, size, room, max_lines);
+        let line_height = fitted.line_height();
+        // Wrapped lines are centred on where the single line would be
+        let py = py - (fitted.lines.len() - 1) as f64 * line_height / 2.0;
+        Placement{ x: px, y: py, size: fitted.size, anchor, lines: fitted.lines, line_height }
+    }).collect()
+}
+
+#[test]
+fn test_layout_stays_inside() {
+    let legend = |slot| Legend{ name: String::new(), text: String::from("MEDIA_EJECT"), slot };
+    let legends: Vec<Legend> = [Slot::Tap, Slot::Hold, Slot::Shifted, Slot::LayerHint,
+                                Slot::TopLeft, Slot::BottomRight]
+        .iter().map(|&slot| legend(slot)).collect();
+
+    for &(width, height) in [(100.0, 100.0), (150.0, 100.0), (100.0, 200.0), (50.0, 50.0)].iter() {
+        let (x, y, w, h) = inside(width, height);
+        let placements = layout(&legends, width, height);
+        for p in &placements {
+            assert!(p.x >= x && p.x <= x + w, "{:?} in {}x{}", p, width, height);
+            let last = p.y + (p.lines.len() - 1) as f64 * p.line_height;
+            assert!(p.y - p.size >= y && last <= y + h, "{:?} in {}x{}", p, width, height);
+            for line in &p.lines {
+                assert!(text_width(line, p.size) <= w, "{:?} in {}x{}", p, width, height);
+            }
+        }
+        // The shifted legend sits above the tap legend
+        assert!(placements[2].y < placements[0].y - placements[0].size);
+    }
+}
@@ -3,13 +3,44 @@ extern crate pest;
 extern crate pest_derive;
 extern crate svg;
 extern crate regex;
+#[macro_use]
+extern crate serde_json;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate toml;
 
-mod types;
+pub mod types;
 pub mod parser;
+pub mod geometry;
+pub mod render;
+pub mod options;
+pub mod theme;
+pub mod category;
+pub mod legend;
+pub mod fit;
+pub mod keycodes;
+pub mod labels;
+pub mod hostlayout;
+pub mod resolve;
+pub mod sim;
+pub mod scenario;
+pub mod lint;
+pub mod graph;
+pub mod find;
+pub mod coverage;
 mod image;
+mod kle;
+mod text;
+
+pub use image::SvgRenderer;
+pub use kle::KleRenderer;
+pub use text::TextRenderer;
 
-use image::*;
 use parser::*;
+use options::RenderOptions;
+use graph::LayerGraph;
+use render::{Keyboard, Renderer};
 
 pub fn to_svg(input: &str) -> String {
     let (kms,am) = parse_string(&input);
@@ -17,3 +48,50 @@ pub fn to_svg(input: &str) -> String {
     // Some change [deleteme]
     Keyboard::new(kms,am).svg()
 }
+
+pub fn to_svg_with(input: &str, options: &RenderOptions) -> String {
+    render(input, options, SvgRenderer::new())
+}
+
+pub fn to_kle(input: &str) -> String {
+    let (kms,am) = parse_string(input);
+
+    Keyboard::new(kms,am).kle()
+}
+
+pub fn to_kle_with(input: &str, options: &RenderOptions) -> String {
+    render(input, options, KleRenderer::new())
+}
+
+/// An HTML page with the diagram and a graph of how its layers lead to
+/// each other
+pub fn to_html_with(input: &str, options: &RenderOptions) -> String {
+    let (kms,am) = parse_string(input);
+
+    let graph = LayerGraph::new(&kms, &am);
+    let problems: String = graph.problems().iter()
+        .map(|p| format!("        <li>{}</li>\n", escape_html(p)))
+        .collect();
+    let svg = Keyboard::with_options(kms,am,options.clone()).render(SvgRenderer::new());
+
+    let mermaid = match options.mermaid_script {
+        Some(ref url) => format!("    <script src=\"{}\"></script>\n", escape_html(url).replace('"', "&quot;")),
+        None => String::new()
+    };
+
+    include_str!("data/page.html")
+        .replace("{mermaid}", &mermaid)
+        .replace("{graph}", &escape_html(&graph.to_mermaid()))
+        .replace("{problems}", &problems)
+        .replace("{svg}", &svg)
+}
+
+fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+pub fn render<R: Renderer>(input: &str, options: &RenderOptions, renderer: R) -> R::Output {
+    let (kms,am) = parse_string(input);
+
+    Keyboard::with_options(kms,am,options.clone()).render(renderer)
+}
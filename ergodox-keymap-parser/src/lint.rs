@@ -0,0 +1,360 @@
+use std::fmt;
+use std::str::FromStr;
+use serde_json::Value;
+
+use types::*;
+use parser::{SourceMap, line_col};
+use resolve::is_transparent;
+use graph::{can_leave, latched};
+use render::target_layer;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Warning,
+    Error
+}
+
+impl Severity {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Severity::Warning => "warning",
+            Severity::Error => "error"
+        }
+    }
+}
+
+/// A change to the source that fixes a finding, by replacing its span
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fix {
+    pub description: String,
+    pub replacement: String
+}
+
+/// Something wrong with a keymap, and where in the source it is
+#[derive(Debug, Clone, PartialEq)]
+pub struct Finding {
+    pub rule: &'static str,
+    pub severity: Severity,
+    pub message: String,
+    /// Byte range in the source, if the finding points at one place
+    pub span: Option<(usize, usize)>,
+    pub fix: Option<Fix>
+}
+
+impl Finding {
+    fn fix(mut self, description: String, replacement: &str) -> Finding {
+        self.fix = Some(Fix{ description, replacement: String::from(replacement) });
+        self
+    }
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "{}[{}]: {}", self.severity.name(), self.rule, self.message)
+    }
+}
+
+/// Every rule, with what it checks
+pub const RULES: &[(&str, &str)] = &[
+    ("missing-action", "An FN key has no fn_actions entry"),
+    ("unused-action", "An fn_actions entry is not used by any key"),
+    ("missing-layer", "An action switches to a layer that does not exist"),
+    ("key-count", "A layer has a different number of keys than the board"),
+    ("base-transparent", "A key on the base layer falls through to nothing"),
+    ("layer-trap", "A layer can be turned on but has no key to leave it"),
+    ("duplicate-action", "Two fn_actions entries do the same thing")
+];
+
+fn finding(rule: &'static str, severity: Severity, message: String, span: Option<(usize, usize)>) -> Finding {
+    Finding{ rule, severity, message, span, fix: None }
+}
+
+// The FN numbers an action refers to itself, as in ACTION_LAYER_TAP_KEY(1, FN2)
+fn action_keys(action: &Action) -> Vec<&Key> {
+    match action {
+        Action::Function(k) | Action::FunctionTap(k) | Action::LayerTapKey(_, k) => vec![k],
+        Action::ModsKey(m, k) | Action::ModsTapKey(m, k) => vec![m, k],
+        _ => vec![]
+    }
+}
+
+// NO spelled the way the key it replaces is, so KC_TRNS becomes KC_NO
+fn no_key(key: &Key) -> &'static str {
+    if key.to_string().starts_with("KC_") { "KC_NO" } else { "NO" }
+}
+
+/// Check a keymap for mistakes. `board_keys` is how many keys each
+/// layer should have.
+pub fn lint(keymaps: &KeyMapVec, actions: &ActionMap, source: &SourceMap, board_keys: usize) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    let mut ids: Vec<u32> = actions.keys().cloned().collect();
+    ids.sort();
+
+    for (layer, keymap) in keymaps.iter().enumerate() {
+        if keymap.len() != board_keys {
+            findings.push(finding("key-count", Severity::Error,
+                                  format!("Layer {} has {} keys but the board has {}", layer, keymap.len(), board_keys),
+                                  source.layers.get(layer).cloned()));
+        }
+        for (index, key) in keymap.iter().enumerate() {
+            match key {
+                Key::Fx(n) if !actions.contains_key(n) =>
+                    findings.push(finding("missing-action", Severity::Error,
+                                          format!("FN{} on layer {} has no fn_actions entry", n, layer),
+                                          source.key(layer, index))
+                                  .fix(format!("Replace FN{} with {}", n, no_key(key)), no_key(key))),
+                _ if layer == 0 && is_transparent(key) =>
+                    findings.push(finding("base-transparent", Severity::Warning,
+                                          format!("{} on the base layer at position {} falls through to nothing", key, index),
+                                          source.key(layer, index))
+                                  .fix(format!("Replace {} with {}", key, no_key(key)), no_key(key))),
+                _ => ()
+            }
+        }
+    }
+
+    let used = |id: u32| {
+        keymaps.iter().flatten()
+            .chain(actions.values().flat_map(action_keys))
+            .any(|key| *key == Key::Fx(id))
+    };
+    for &id in &ids {
+        let action = &actions[&id];
+        let span = source.actions.get(&id).cloned();
+
+        if !used(id) {
+            findings.push(finding("unused-action", Severity::Warning,
+                                  format!("FN{} is not used by any key", id), span));
+        }
+        if let Some(layer) = target_layer(action).filter(|&l| l as usize >= keymaps.len()) {
+            findings.push(finding("missing-layer", Severity::Error,
+                                  format!("FN{} switches to layer {} but there are only {}", id, layer, keymaps.len()),
+                                  span));
+        }
+        if let Some(first) = ids.iter().find(|&&other| other < id && actions[&other] == *action) {
+            findings.push(finding("duplicate-action", Severity::Warning,
+                                  format!("FN{} does the same as FN{}", id, first), span));
+        }
+
+        if let Some((layer, state)) = latched(action) {
+            if layer != 0 && (layer as usize) < keymaps.len() && used(id)
+                && !can_leave(keymaps, actions, state, layer) {
+                findings.push(finding("layer-trap", Severity::Error,
+                                      format!("FN{} turns on layer {} but no key there turns it off", id, layer),
+                                      span));
+            }
+        }
+    }
+
+    findings
+}
+
+/// How findings are written out
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    /// One line each, as compilers write them
+    Text,
+    Json,
+    /// SARIF 2.1.0, for code scanning
+    Sarif,
+    /// GitHub Actions workflow commands, which annotate pull requests
+    Github
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Format, String> {
+        match s {
+            "text" => Ok(Format::Text),
+            "json" => Ok(Format::Json),
+            "sarif" => Ok(Format::Sarif),
+            "github" => Ok(Format::Github),
+            _ => Err(format!("Unknown format: {}", s))
+        }
+    }
+}
+
+// Start and end line and column of a span
+fn region(input: &str, (start, end): (usize, usize)) -> (usize, usize, usize, usize) {
+    let (line, col) = line_col(input, start);
+    let (end_line, end_col) = line_col(input, end);
+    (line, col, end_line, end_col)
+}
+
+/// Write findings in `file`, whose contents are `input`
+pub fn report(findings: &[Finding], file: &str, input: &str, format: Format) -> String {
+    match format {
+        Format::Text => findings.iter().map(|finding| match finding.span {
+            Some((start, _)) => {
+                let (line, col) = line_col(input, start);
+                format!("{}:{}:{}: {}\n", file, line, col, finding)
+            }
+            None => format!("{}: {}\n", file, finding)
+        }).collect(),
+        Format::Json => {
+            let findings: Vec<Value> = findings.iter().map(|finding| {
+                let mut json = json!({
+                    "rule": finding.rule,
+                    "severity": finding.severity.name(),
+                    "message": finding.message,
+                    "file": file
+                });
+                if let Some(span) = finding.span {
+                    let (line, col, end_line, end_col) = region(input, span);
+                    json["line"] = json!(line);
+                    json["column"] = json!(col);
+                    json["end_line"] = json!(end_line);
+                    json["end_column"] = json!(end_col);
+                }
+                if let Some(fix) = &finding.fix {
+                    json["fix"] = json!({"description": fix.description, "replacement": fix.replacement});
+                }
+                json
+            }).collect();
+            format!("{:#}\n", Value::Array(findings))
+        }
+        Format::Sarif => format!("{:#}\n", sarif(findings, file, input)),
+        Format::Github => findings.iter().map(|finding| {
+            let mut properties = format!("file={}", escape_property(file));
+            if let Some(span) = finding.span {
+                let (line, col, end_line, end_col) = region(input, span);
+                properties.push_str(&format!(",line={},col={},endLine={},endColumn={}",
+                                             line, col, end_line, end_col));
+            }
+            let mut message = finding.message.clone();
+            if let Some(fix) = &finding.fix {
+                message.push_str(&format!(" ({})", fix.description));
+            }
+            format!("::{} {},title={}::{}\n", finding.severity.name(), properties,
+                    escape_property(finding.rule), escape_data(&message))
+        }).collect()
+    }
+}
+
+// Workflow commands need these escaped in messages, and `:` and `,` too
+// in properties
+fn escape_data(s: &str) -> String {
+    s.replace('%', "%25").replace('\r', "%0D").replace('\n', "%0A")
+}
+
+fn escape_property(s: &str) -> String {
+    escape_data(s).replace(':', "%3A").replace(',', "%2C")
+}
+
+fn sarif(findings: &[Finding], file: &str, input: &str) -> Value {
+    let rules: Vec<Value> = RULES.iter()
+        .map(|&(id, description)| json!({"id": id, "shortDescription": {"text": description}}))
+        .collect();
+    let results: Vec<Value> = findings.iter().map(|finding| {
+        let mut result = json!({
+            "ruleId": finding.rule,
+            "ruleIndex": RULES.iter().position(|r| r.0 == finding.rule),
+            "level": finding.severity.name(),
+            "message": {"text": finding.message},
+            "locations": [{"physicalLocation": {"artifactLocation": {"uri": file}}}]
+        });
+        if let Some(span) = finding.span {
+            let (line, col, end_line, end_col) = region(input, span);
+            let region = json!({"startLine": line, "startColumn": col, "endLine": end_line, "endColumn": end_col});
+            result["locations"][0]["physicalLocation"]["region"] = region.clone();
+            if let Some(fix) = &finding.fix {
+                result["fixes"] = json!([{
+                    "description": {"text": fix.description},
+                    "artifactChanges": [{
+                        "artifactLocation": {"uri": file},
+                        "replacements": [{"deletedRegion": region, "insertedContent": {"text": fix.replacement}}]
+                    }]
+                }]);
+            }
+        }
+        result
+    }).collect();
+
+    json!({
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {"driver": {"name": "ergowhat", "version": env!("CARGO_PKG_VERSION"), "rules": rules}},
+            "results": results
+        }]
+    })
+}
+
+#[test]
+fn test_lint() {
+    use parser::parse_with_source;
+
+    let input = "keymaps[][MATRIX_ROWS][MATRIX_COLS] = {\n\
+                 KEYMAP(TRNS, FN0, FN1, FN9),\n\
+                 KEYMAP(A, TRNS, TRNS, B),\n\
+                 KEYMAP(A, FN0, FN3),\n\
+                 };\n\
+                 fn_actions[] = {\n\
+                 [0] = ACTION_LAYER_TOGGLE(1),\n\
+                 [1] = ACTION_LAYER_SET(2, ON_PRESS),\n\
+                 [2] = ACTION_LAYER_MOMENTARY(5),\n\
+                 [3] = ACTION_LAYER_TOGGLE(1),\n\
+                 };\n";
+    let (keymaps, actions, source) = parse_with_source(input);
+    let findings = lint(&keymaps, &actions, &source, 4);
+    let found: Vec<(&str, String)> = findings.iter().map(|f| (f.rule, f.message.clone())).collect();
+    let entry = |rule, message: &str| (rule, String::from(message));
+    assert_eq!(found, vec![
+        entry("base-transparent", "TRNS on the base layer at position 0 falls through to nothing"),
+        entry("missing-action", "FN9 on layer 0 has no fn_actions entry"),
+        entry("key-count", "Layer 2 has 3 keys but the board has 4"),
+        entry("layer-trap", "FN1 turns on layer 2 but no key there turns it off"),
+        entry("unused-action", "FN2 is not used by any key"),
+        entry("missing-layer", "FN2 switches to layer 5 but there are only 3"),
+        entry("duplicate-action", "FN3 does the same as FN0")
+    ]);
+
+    let (start, end) = findings[1].span.unwrap();
+    assert_eq!(&input[start..end], "FN9");
+    assert_eq!(findings[0].fix, Some(Fix{ description: String::from("Replace TRNS with NO"),
+                                          replacement: String::from("NO") }));
+    assert_eq!(findings[1].fix.as_ref().map(|f| f.replacement.as_str()), Some("NO"));
+
+    // Keymaps written with the KC_ prefix keep it
+    let input = "keymaps[][MATRIX_ROWS][MATRIX_COLS] = {\n\
+                 KEYMAP(KC_TRNS, KC_A),\n\
+                 };\n\
+                 fn_actions[] = {\n\
+                 };\n";
+    let (keymaps, actions, source) = parse_with_source(input);
+    let findings = lint(&keymaps, &actions, &source, 2);
+    assert_eq!(findings[0].fix, Some(Fix{ description: String::from("Replace KC_TRNS with KC_NO"),
+                                          replacement: String::from("KC_NO") }));
+}
+
+#[test]
+fn test_report() {
+    let input = "KEYMAP(\n  TRNS, A)";
+    let findings = vec![
+        finding("base-transparent", Severity::Warning, String::from("TRNS falls through"), Some((10, 14)))
+            .fix(String::from("Replace TRNS with NO"), "NO"),
+        finding("key-count", Severity::Error, String::from("Layer 0 has 2 keys, not 3"), None)
+    ];
+
+    assert_eq!(report(&findings, "km.c", input, Format::Text),
+               "km.c:2:3: warning[base-transparent]: TRNS falls through\n\
+                km.c: error[key-count]: Layer 0 has 2 keys, not 3\n");
+    assert_eq!(report(&findings, "km.c", input, Format::Github),
+               "::warning file=km.c,line=2,col=3,endLine=2,endColumn=7,title=base-transparent::\
+                TRNS falls through (Replace TRNS with NO)\n\
+                ::error file=km.c,title=key-count::Layer 0 has 2 keys, not 3\n");
+
+    let json: Value = ::serde_json::from_str(&report(&findings, "km.c", input, Format::Json)).unwrap();
+    assert_eq!(json[0]["end_column"], 7);
+    assert_eq!(json[0]["fix"]["replacement"], "NO");
+    assert_eq!(json[1].get("line"), None);
+
+    let sarif: Value = ::serde_json::from_str(&report(&findings, "km.c", input, Format::Sarif)).unwrap();
+    let result = &sarif["runs"][0]["results"][0];
+    assert_eq!(result["level"], "warning");
+    assert_eq!(result["locations"][0]["physicalLocation"]["region"]["startLine"], 2);
+    assert_eq!(result["fixes"][0]["artifactChanges"][0]["replacements"][0]["insertedContent"]["text"], "NO");
+    assert_eq!(sarif["runs"][0]["tool"]["driver"]["rules"][result["ruleIndex"].as_u64().unwrap() as usize]["id"],
+               "base-transparent");
+}
@@ -0,0 +1,100 @@
+use std::str::FromStr;
+
+use geometry::Geometry;
+use theme::Theme;
+use labels::LabelMap;
+use hostlayout::HostLayout;
+
+/// Physical size of a 1u key in the output, such as 19.05mm
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeySize {
+    pub length: f64,
+    pub unit: String
+}
+
+impl FromStr for KeySize {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<KeySize, String> {
+        let s = s.trim();
+        let split = s.find(|c: char| !(c.is_ascii_digit() || c == '.')).unwrap_or(s.len());
+        let (length, unit) = s.split_at(split);
+        let length = f64::from_str(length).map_err(|_| format!("Invalid key size: {}", s))?;
+        Ok(KeySize{ length, unit: String::from(unit) })
+    }
+}
+
+/// Operating system whose names for the modifiers are shown
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Os {
+    Mac,
+    Windows,
+    Linux
+}
+
+impl FromStr for Os {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Os, String> {
+        match s {
+            "mac" | "macos" => Ok(Os::Mac),
+            "win" | "windows" => Ok(Os::Windows),
+            "linux" => Ok(Os::Linux),
+            _ => Err(format!("Unknown operating system: {}", s))
+        }
+    }
+}
+
+/// Controls what is drawn and how
+#[derive(Debug, Clone)]
+pub struct RenderOptions {
+    pub theme: Theme,
+    // Layers to draw, in order. All layers are drawn when empty.
+    pub layers: Vec<usize>,
+    // Embed the layer switching script. Without it every layer is
+    // drawn, one below the other.
+    pub script: bool,
+    // Legends to use instead of the built in ones
+    pub labels: LabelMap,
+    // Draw modifiers as this system names them, rather than generically
+    pub os: Option<Os>,
+    // Show what keys type on this layout rather than on US QWERTY
+    pub host_layout: Option<HostLayout>,
+    pub geometry: Geometry,
+    // Size of the document, when it should not just fill its container
+    pub key_size: Option<KeySize>,
+    // Where HTML output loads Mermaid from to draw the layer graph.
+    // Without it the graph is left as Mermaid text, so the page works
+    // offline.
+    pub mermaid_script: Option<String>
+}
+
+impl Default for RenderOptions {
+    fn default() -> RenderOptions {
+        RenderOptions{
+            theme: Theme::default(),
+            layers: Vec::new(),
+            script: true,
+            labels: LabelMap::new(),
+            os: None,
+            host_layout: None,
+            geometry: Geometry::ergodox(),
+            key_size: None,
+            mermaid_script: None
+        }
+    }
+}
+
+#[test]
+fn test_key_size() {
+    assert_eq!(KeySize::from_str("19.05mm"), Ok(KeySize{ length: 19.05, unit: String::from("mm") }));
+    assert_eq!(KeySize::from_str("64"), Ok(KeySize{ length: 64.0, unit: String::new() }));
+    assert!(KeySize::from_str("mm").is_err());
+}
+
+#[test]
+fn test_os() {
+    assert_eq!(Os::from_str("mac"), Ok(Os::Mac));
+    assert_eq!(Os::from_str("windows"), Ok(Os::Windows));
+    assert!(Os::from_str("beos").is_err());
+}
@@ -1,4 +1,5 @@
-use regex::Regex;
+use regex::{Captures, Regex};
+use std::collections::HashMap;
 use std::str::FromStr;
 
 use types::*;
@@ -90,7 +91,7 @@ fn parse_actions(input: &str) -> ActionMap {
                 let mut xs1 = inner[1].clone().into_inner();
                 let k1 = match inner[1].as_rule() {
                     Rule::fn_key => Key::Fx(u32::from_str( xs1.next().unwrap().as_str() ).unwrap()),
-                    Rule::named_key => Key::Key(String::from( inner[0].as_str())),
+                    Rule::named_key => Key::Key(String::from( inner[1].as_str())),
                     _ => panic!("What? {:?}", rule1)
                 };
                 
@@ -146,17 +147,89 @@ fn parse_keymaps(input: &str) -> KeyMapVec {
     kmv
 }
 
-pub fn parse_string(input: &str) -> (KeyMapVec, ActionMap) {
+/// Where the parts of a keymap are in its source, as byte ranges
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SourceMap {
+    /// Each KEYMAP(...) by layer
+    pub layers: Vec<(usize, usize)>,
+    /// Each key by layer and position
+    pub keys: Vec<Vec<(usize, usize)>>,
+    /// Each fn_actions entry by its FN number
+    pub actions: HashMap<u32, (usize, usize)>
+}
+
+impl SourceMap {
+    pub fn key(&self, layer: usize, index: usize) -> Option<(usize, usize)> {
+        self.keys.get(layer).and_then(|keys| keys.get(index)).cloned()
+    }
+}
 
-    // Strip out line comments
+// Blank out line comments, keeping their newlines so that byte offsets
+// and line numbers still match the source
+fn strip_comments(input: &str) -> String {
     let line_comment_re = Regex::new(r"//(.*)\n").unwrap();
 
-    let processed = line_comment_re.replace_all(&input, "");
+    line_comment_re.replace_all(input, |caps: &Captures| {
+        format!("{}\n", " ".repeat(caps[0].len() - 1))
+    }).into_owned()
+}
+
+fn source_map(input: &str) -> SourceMap {
+    let mut source = SourceMap::default();
+
+    let keymaps = KeymapParser::parse(Rule::keymaps, input).unwrap_or_else(|e| panic!("{}", e))
+        .flatten()
+        .next()
+        .unwrap();
+    for map in keymaps.into_inner() {
+        let span = map.clone().into_span();
+        source.layers.push((span.start(), span.end()));
+        source.keys.push(map.into_inner()
+                         .map(|entry| entry.into_span())
+                         .map(|span| (span.start(), span.end()))
+                         .collect());
+    }
+
+    let actions = KeymapParser::parse(Rule::fn_actions, input).unwrap_or_else(|e| panic!("{}", e));
+    for action in actions {
+        let span = action.clone().into_span();
+        let idx = u32::from_str(action.into_inner().next().unwrap().as_str()).unwrap();
+        source.actions.insert(idx, (span.start(), span.end()));
+    }
+    source
+}
+
+/// The line and column, counting from 1, of a byte offset
+pub fn line_col(input: &str, offset: usize) -> (usize, usize) {
+    let before = &input[..offset.min(input.len())];
+    let line = before.matches('\n').count() + 1;
+    let col = before.rsplit('\n').next().map_or(0, |l| l.chars().count()) + 1;
+    (line, col)
+}
+
+pub fn parse_string(input: &str) -> (KeyMapVec, ActionMap) {
+    let processed = strip_comments(input);
 
     (parse_keymaps(&processed),
      parse_actions(&processed))
 }
 
+/// Parse the insides of keymaps[] and fn_actions[], for tests
+#[cfg(test)]
+pub fn parse_test_keymap(keymaps: &str, actions: &str) -> (KeyMapVec, ActionMap) {
+    parse_string(&format!("keymaps[][MATRIX_ROWS][MATRIX_COLS] = {{\n{}\n}};\nfn_actions[] = {{\n{}\n}};\n",
+                          keymaps, actions))
+}
+
+/// Parse a keymap, noting where each part of it is in `input`
+pub fn parse_with_source(input: &str) -> (KeyMapVec, ActionMap, SourceMap) {
+    let processed = strip_comments(input);
+
+    (parse_keymaps(&processed),
+     parse_actions(&processed),
+     source_map(&processed))
+}
+
 #[test]
 fn test_keymap() {
     parses_to! {
@@ -174,6 +247,31 @@ fn test_keymap() {
     }
 }
 
+#[test]
+fn test_mods_key() {
+    let input = "fn_actions[] = {\n    [1] = ACTION_MODS_KEY(MOD_LCTL, KC_X),\n};\n";
+    let actions = parse_actions(input);
+    assert_eq!(actions.get(&1), Some(&Action::ModsKey(Key::Key(String::from("MOD_LCTL")),
+                                                     Key::Key(String::from("KC_X")))));
+}
+
+#[test]
+fn test_source_map() {
+    let input = "keymaps[][MATRIX_ROWS][MATRIX_COLS] = {\n    // base\n    KEYMAP(A, FN1),\n};\n\
+                 fn_actions[] = {\n    [1] = ACTION_LAYER_MOMENTARY(1), // sym\n};\n";
+    let (keymaps, actions, source) = parse_with_source(input);
+    assert_eq!(keymaps, vec![vec![Key::Key(String::from("A")), Key::Fx(1)]]);
+    assert_eq!(actions.get(&1), Some(&Action::LayerMomentary(1)));
+
+    let (start, end) = source.key(0, 1).unwrap();
+    assert_eq!(&input[start..end], "FN1");
+    assert_eq!(line_col(input, start), (3, 15));
+    let (start, end) = source.actions[&1];
+    assert_eq!(&input[start..end], "[1] = ACTION_LAYER_MOMENTARY(1)");
+    assert_eq!(line_col(input, start), (6, 5));
+    assert_eq!(&input[source.layers[0].0..source.layers[0].1], "KEYMAP(A, FN1)");
+}
+
 #[test]
 fn test_integer() {
     parses_to! {
@@ -0,0 +1,236 @@
+use types::*;
+use geometry::*;
+use options::*;
+use category::Category;
+use legend::*;
+use keycodes;
+use hostlayout::Levels;
+use resolve::*;
+
+pub struct Keyboard {
+    pub(crate) keymaps: KeyMapVec,
+    pub(crate) actions: ActionMap,
+    pub(crate) options: RenderOptions
+}
+
+/// How pressing a key changes the visible layers
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LayerSwitch {
+    // Turn the layer on
+    On(u32),
+    // Show only this layer
+    Only(u32),
+    // Show the layer while the key is held
+    Momentary(u32)
+}
+
+/// Everything a renderer needs to draw one key of one layer
+pub struct KeyView<'a> {
+    pub layer: usize,
+    pub index: usize,
+    pub position: &'a KeyPosition,
+    pub key: &'a Key,
+    pub action: Option<&'a Action>,
+    pub category: Category,
+    // Layer that this key switches to, if it is a layer key
+    pub target: Option<u32>,
+    pub legends: Vec<Legend>,
+    pub switch: Option<LayerSwitch>,
+    // For a transparent key, the layer its legends and action come from
+    pub inherited: Option<usize>
+}
+
+/// An output format. The chosen layers are walked in order and every
+/// key with a position in the geometry is passed to `key`.
+pub trait Renderer {
+    type Output;
+
+    fn begin(&mut self, _options: &RenderOptions) {}
+    fn begin_layer(&mut self, _layer: usize) {}
+    fn key(&mut self, key: &KeyView);
+    fn end_layer(&mut self, _layer: usize) {}
+    fn finish(self) -> Self::Output;
+}
+
+/// Normal and shifted legends for a keycode
+pub fn textoutput(input: &str) -> (String,String) {
+    keycodes::legends(input)
+}
+
+// Marks a key whose legends come from a layer below
+const TRANSPARENT_MARKER: &str = "▽";
+
+/// The layer a layer action switches to
+pub fn target_layer(action: &Action) -> Option<u32> {
+    match action {
+        Action::DefaultLayerSet(layer)
+            | Action::LayerSet(layer, _)
+            | Action::LayerSetClear(layer)
+            | Action::LayerMomentary(layer)
+            | Action::LayerTapKey(layer, _)
+            | Action::LayerTapToggle(layer)
+            | Action::LayerToggle(layer) => Some(*layer),
+        _ => None
+    }
+}
+
+// Whether modifiers, combined with `|`, are only Shift
+fn is_shift(mods: &str) -> bool {
+    mods.split('|')
+        .all(|m| matches!(keycodes::lookup(m.trim()).map(keycodes::Keycode::name), Some("LSFT") | Some("RSFT")))
+}
+
+impl Keyboard {
+
+    pub fn new(keymaps: KeyMapVec, actions: ActionMap) -> Keyboard {
+        Keyboard::with_options(keymaps, actions, RenderOptions::default())
+    }
+
+    pub fn with_options(keymaps: KeyMapVec, actions: ActionMap, options: RenderOptions) -> Keyboard {
+        Keyboard{ keymaps, actions, options }
+    }
+
+    // The legends for a keycode in a slot. Keycodes that mean something
+    // else with shift or AltGr get legends for those as well when tapped.
+    fn legends<S: Into<String>>(&self, name: S, slot: Slot) -> Vec<Legend> {
+        let name = name.into();
+        let host = self.options.host_layout.as_ref().and_then(|h| {
+            // QMK's shifted keycodes type what their key does with Shift
+            match keycodes::lookup(&name).and_then(|k| k.shift_of) {
+                Some(base) => h.shifted(base).map(|text| Levels{ normal: String::from(text), ..Levels::default() }),
+                None => h.levels(&name).cloned()
+            }
+        });
+        let (normal, shifted, altgr) = match (self.options.labels.key(&name), host) {
+            (Some(label), _) => (String::from(label), String::new(), String::new()),
+            (None, Some(levels)) => (levels.normal.clone(), levels.shifted.clone(), levels.altgr.clone()),
+            (None, None) => {
+                let (normal, shifted) = keycodes::legends_for(&name, self.options.os);
+                (normal, shifted, String::new())
+            }
+        };
+
+        let mut legends = vec![Legend{ name: name.clone(), text: normal, slot }];
+        if slot == Slot::Tap {
+            for (text, slot) in [(shifted, Slot::Shifted), (altgr, Slot::BottomRight)].iter() {
+                if !text.is_empty() {
+                    legends.push(Legend{ name: name.clone(), text: text.clone(), slot: *slot });
+                }
+            }
+        }
+        legends
+    }
+
+    fn keyview(&self, layer: usize, index: usize) -> KeyView<'_> {
+        let own = &self.keymaps[layer][index];
+        // Transparent keys show the key they fall through to, as if
+        // every layer up to this one were on
+        let (source, key, action) = match resolve(&self.keymaps, &self.actions,
+                                                  LayerState::up_to(layer as u32), index) {
+            Some(resolved) if is_transparent(own) && !is_transparent(resolved.key) =>
+                (resolved.layer, resolved.key, resolved.action),
+            _ => (layer, own, match own {
+                Key::Fx(n) => self.actions.get(n),
+                Key::Key(_) => None
+            })
+        };
+
+        let (legends, switch) = match (key, action) {
+            (Key::Key(name), _) =>
+                (self.legends(name.as_str(), Slot::Tap), None),
+            (Key::Fx(_), None) =>
+                (self.legends("BROKEN", Slot::Tap), None),
+            (_, Some(Action::LayerSet(layer, _))) =>
+                (self.legends(format!("#{}", layer), Slot::Tap),
+                 Some(LayerSwitch::On(*layer))),
+            (_, Some(Action::LayerSetClear(layer))) =>
+                (self.legends(format!("#{}!", layer), Slot::Tap),
+                 Some(LayerSwitch::Only(*layer))),
+            (_, Some(Action::LayerMomentary(layer))) =>
+                (self.legends(format!("~{}", layer), Slot::Tap),
+                 Some(LayerSwitch::Momentary(*layer))),
+            (_, Some(Action::LayerTapKey(layer, k))) => {
+                let tap = match k {
+                    Key::Key(name) => name.as_str(),
+                    _ => "WHAT?"
+                };
+                let mut legends = self.legends(tap, Slot::Tap);
+                legends.extend(self.legends(format!("~L{}", layer), Slot::LayerHint));
+                (legends, Some(LayerSwitch::Momentary(*layer)))
+            }
+            (_, Some(Action::ModsKey(m, k))) => {
+                let mods = m.to_string();
+                let shifted = self.options.host_layout.as_ref()
+                    .filter(|_| is_shift(&mods))
+                    .and_then(|h| h.shifted(&k.to_string()));
+                let text = match shifted {
+                    Some(text) => String::from(text),
+                    None => {
+                        let tap = self.legends(k.to_string(), Slot::Tap).remove(0).text;
+                        keycodes::chord(&mods, &tap, self.options.os)
+                    }
+                };
+                (vec![Legend{ name: key.to_string(), text, slot: Slot::Tap }], None)
+            }
+            (_, Some(Action::ModsTapKey(m, k))) => {
+                let mut legends = self.legends(k.to_string(), Slot::Tap);
+                legends.extend(self.legends(m.to_string(), Slot::Hold));
+                (legends, None)
+            }
+            (_, Some(_)) =>
+                (self.legends(key.to_string(), Slot::Tap), None)
+        };
+
+        // A label for the position or action replaces everything else
+        let labels = &self.options.labels;
+        let label = labels.position(layer, index)
+            .or_else(|| action.and_then(|a| labels.action(key, a)));
+        let mut legends = match label {
+            Some(text) => vec![Legend{ name: key.to_string(), text: String::from(text), slot: Slot::Tap }],
+            None => legends
+        };
+        let inherited = if source == layer { None } else { Some(source) };
+        if inherited.is_some() {
+            legends.push(Legend{ name: own.to_string(), text: String::from(TRANSPARENT_MARKER), slot: Slot::TopLeft });
+        }
+
+        KeyView{
+            layer,
+            index,
+            position: &self.options.geometry.keys[index],
+            key: own,
+            action,
+            category: Category::of_key(own, action),
+            target: action.and_then(target_layer),
+            legends,
+            switch,
+            inherited
+        }
+    }
+
+    // Layers chosen by the options that exist in the keymap
+    fn layers(&self) -> Vec<usize> {
+        if self.options.layers.is_empty() {
+            (0..self.keymaps.len()).collect()
+        } else {
+            self.options.layers.iter()
+                .cloned()
+                .filter(|&layer| layer < self.keymaps.len())
+                .collect()
+        }
+    }
+
+    /// Walk the chosen layers and their keys through a renderer
+    pub fn render<R: Renderer>(&self, mut renderer: R) -> R::Output {
+        renderer.begin(&self.options);
+        for layer in self.layers() {
+            let keys = self.keymaps[layer].len().min(self.options.geometry.keys.len());
+            renderer.begin_layer(layer);
+            for index in 0..keys {
+                renderer.key(&self.keyview(layer, index));
+            }
+            renderer.end_layer(layer);
+        }
+        renderer.finish()
+    }
+}
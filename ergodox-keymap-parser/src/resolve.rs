@@ -0,0 +1,141 @@
+use types::*;
+use keycodes;
+
+/// Which layers are on, kept as TMK does: a bit for every layer turned
+/// on by a layer key, and the default layer underneath them all.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct LayerState {
+    pub layers: u32,
+    pub default: u32
+}
+
+impl LayerState {
+    /// Only the default layer on
+    pub fn new(default: u32) -> LayerState {
+        LayerState{ layers: 0, default }
+    }
+
+    /// The default layer and every layer below `layer` on, which is how
+    /// a diagram of `layer` reads
+    pub fn up_to(layer: u32) -> LayerState {
+        let layers = if layer >= 31 { !0 } else { (1 << (layer + 1)) - 1 };
+        LayerState{ layers, default: 0 }
+    }
+
+    pub fn is_on(&self, layer: u32) -> bool {
+        layer == self.default || (layer < 32 && self.layers & (1 << layer) != 0)
+    }
+
+    pub fn on(&mut self, layer: u32) {
+        if layer < 32 {
+            self.layers |= 1 << layer;
+        }
+    }
+
+    pub fn off(&mut self, layer: u32) {
+        if layer < 32 {
+            self.layers &= !(1 << layer);
+        }
+    }
+
+    pub fn toggle(&mut self, layer: u32) {
+        if layer < 32 {
+            self.layers ^= 1 << layer;
+        }
+    }
+
+    /// Turn off every layer but `layer`
+    pub fn only(&mut self, layer: u32) {
+        self.layers = 0;
+        self.on(layer);
+    }
+
+    /// The highest layer that is on
+    pub fn highest(&self) -> u32 {
+        (0..32).rev().find(|&l| self.is_on(l)).unwrap_or(0)
+    }
+
+    /// Layers that are on, highest first
+    pub fn active(&self) -> Vec<u32> {
+        (0..32).rev().filter(|&l| self.is_on(l)).collect()
+    }
+}
+
+/// Whether a key falls through to the layers below
+pub fn is_transparent(key: &Key) -> bool {
+    match key {
+        Key::Key(name) => keycodes::lookup(name).is_some_and(|k| k.name() == "TRNS"),
+        Key::Fx(_) => false
+    }
+}
+
+/// The key a position takes in some layer state, and the layer it
+/// came from
+#[derive(Debug, PartialEq)]
+pub struct Resolved<'a> {
+    pub layer: usize,
+    pub index: usize,
+    pub key: &'a Key,
+    pub action: Option<&'a Action>
+}
+
+/// The key at `index` for a layer state. The highest layer that is on
+/// and not transparent there wins; when every layer is transparent the
+/// key on layer 0 is used, as TMK does.
+pub fn resolve<'a>(keymaps: &'a KeyMapVec, actions: &'a ActionMap,
+                   state: LayerState, index: usize) -> Option<Resolved<'a>> {
+    let found = state.active().into_iter()
+        .map(|l| l as usize)
+        .filter_map(|l| keymaps.get(l).and_then(|km| km.get(index)).map(|k| (l, k)))
+        .find(|&(_, key)| !is_transparent(key));
+    let (layer, key) = match found {
+        Some(found) => found,
+        None => (0, keymaps.first()?.get(index)?)
+    };
+    let action = match key {
+        Key::Fx(n) => actions.get(n),
+        Key::Key(_) => None
+    };
+    Some(Resolved{ layer, index, key, action })
+}
+
+/// Every position of the keymap as it behaves in a layer state
+pub fn effective_keymap<'a>(keymaps: &'a KeyMapVec, actions: &'a ActionMap,
+                            state: LayerState) -> Vec<Resolved<'a>> {
+    let keys = keymaps.first().map_or(0, Vec::len);
+    (0..keys).filter_map(|index| resolve(keymaps, actions, state, index)).collect()
+}
+
+#[test]
+fn test_effective_keymap() {
+    let (keymaps, actions) = ::parser::parse_test_keymap("KEYMAP(KC_A, KC_B, KC_TRNS),\n\
+                                                          KEYMAP(KC_1, KC_TRNS, KC_TRNS),\n\
+                                                          KEYMAP(KC_TRNS, KC_TRNS, FN0)",
+                                                         "[0] = ACTION_LAYER_TOGGLE(2)");
+
+    let names = |state| -> Vec<(usize, String)> {
+        effective_keymap(&keymaps, &actions, state).iter()
+            .map(|r| (r.layer, r.key.to_string()))
+            .collect()
+    };
+    let entry = |layer, name: &str| (layer, String::from(name));
+
+    assert_eq!(names(LayerState::new(0)),
+               vec![entry(0, "KC_A"), entry(0, "KC_B"), entry(0, "KC_TRNS")]);
+
+    // Layer 1 is skipped when it is off, even under a transparent key
+    let mut state = LayerState::new(0);
+    state.on(2);
+    assert_eq!(names(state), vec![entry(0, "KC_A"), entry(0, "KC_B"), entry(2, "FN0")]);
+    assert_eq!(resolve(&keymaps, &actions, state, 2).unwrap().action, Some(&Action::LayerToggle(2)));
+
+    state.on(1);
+    assert_eq!(names(state), vec![entry(1, "KC_1"), entry(0, "KC_B"), entry(2, "FN0")]);
+
+    // The default layer counts as on
+    assert_eq!(names(LayerState::new(1)),
+               vec![entry(1, "KC_1"), entry(0, "KC_B"), entry(0, "KC_TRNS")]);
+
+    assert_eq!(LayerState::up_to(1).active(), vec![1, 0]);
+    assert_eq!(resolve(&keymaps, &actions, state, 9), None);
+}
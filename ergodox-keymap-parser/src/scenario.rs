@@ -0,0 +1,231 @@
+use toml;
+
+use types::*;
+use keycodes;
+use sim::*;
+
+/// How long each step of a scenario takes, in milliseconds
+pub const STEP: u32 = 10;
+
+/// Scenarios to run against a keymap, read from TOML:
+///
+/// ```toml
+/// keymap = "keymap.c"
+///
+/// [[scenario]]
+/// name = "hold L_SPC, tap J emits DOWN"
+/// steps = ["hold FN4", "tap J", "release FN4"]
+/// keys = ["DOWN"]
+/// layers = []
+/// ```
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ScenarioFile {
+    /// The keymap under test, relative to the scenario file
+    pub keymap: String,
+    pub tapping_term: Option<u32>,
+    pub tapping_toggle: Option<u32>,
+    #[serde(default)]
+    pub permissive_hold: bool,
+    #[serde(rename = "scenario", default)]
+    pub scenarios: Vec<Scenario>
+}
+
+/// Keys to press and what should come of them. Steps are `press KEY`,
+/// `release KEY`, `tap KEY`, `hold KEY` (press it past the tapping term)
+/// and `wait MS`. A key is named as written in the keymap, searching
+/// from the base layer up, or by its position as `#12`.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Scenario {
+    pub name: String,
+    pub steps: Vec<String>,
+    /// Keycodes sent to the host, in the order they go down
+    pub keys: Option<Vec<String>>,
+    /// Layers turned on over the default layer once the steps are done
+    pub layers: Option<Vec<u32>>,
+    /// Default layer once the steps are done
+    pub default: Option<u32>
+}
+
+/// How a scenario went; it passed if there are no failures
+#[derive(Debug, Clone, PartialEq)]
+pub struct Outcome {
+    pub name: String,
+    pub failures: Vec<String>
+}
+
+impl Outcome {
+    pub fn passed(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
+
+impl ScenarioFile {
+    pub fn from_toml(input: &str) -> Result<ScenarioFile, String> {
+        toml::from_str(input).map_err(|e| e.to_string())
+    }
+
+    /// Run every scenario on a fresh keyboard
+    pub fn run(&self, keymaps: &KeyMapVec, actions: &ActionMap) -> Vec<Outcome> {
+        self.scenarios.iter().map(|scenario| {
+            let mut sim = Simulator::new(keymaps.clone(), actions.clone());
+            sim.tapping_term = self.tapping_term.unwrap_or(TAPPING_TERM);
+            sim.tapping_toggle = self.tapping_toggle.unwrap_or(TAPPING_TOGGLE);
+            sim.permissive_hold = self.permissive_hold;
+            scenario.run(keymaps, sim)
+        }).collect()
+    }
+}
+
+impl Scenario {
+    fn run(&self, keymaps: &KeyMapVec, mut sim: Simulator) -> Outcome {
+        let mut outcome = Outcome{ name: self.name.clone(), failures: Vec::new() };
+
+        let mut time = 0;
+        for step in &self.steps {
+            match events(step, keymaps, time, sim.tapping_term) {
+                Ok((events, end)) => {
+                    for event in events {
+                        sim.event(event);
+                    }
+                    sim.advance(end);
+                    time = end;
+                }
+                Err(e) => {
+                    outcome.failures.push(format!("{}: {}", step, e));
+                    return outcome;
+                }
+            }
+        }
+
+        if let Some(expected) = &self.keys {
+            let sent: Vec<(String, String)> = sim.outputs().iter().filter_map(|output| match output {
+                Output::KeyDown{ code, layer, index, .. } =>
+                    Some((code.clone(), format!("{} (layer {}, position {})", code, layer, index))),
+                _ => None
+            }).collect();
+            let expected: Vec<String> = expected.iter().map(|k| keycodes::canonical(k)).collect();
+            if sent.iter().map(|s| &s.0).ne(expected.iter()) {
+                let sent: Vec<&str> = sent.iter().map(|s| s.1.as_str()).collect();
+                outcome.failures.push(format!("expected keys {}, got {}",
+                                              list(&expected), list(&sent)));
+            }
+        }
+        let state = sim.state();
+        if let Some(expected) = &self.layers {
+            let mut expected = expected.clone();
+            expected.sort();
+            expected.dedup();
+            let on: Vec<u32> = (0..32).filter(|&l| state.layers & (1 << l) != 0).collect();
+            if expected != on {
+                outcome.failures.push(format!("expected layers {}, got {}", list(&expected), list(&on)));
+            }
+        }
+        if let Some(expected) = self.default {
+            if expected != state.default {
+                outcome.failures.push(format!("expected default layer {}, got {}",
+                                              expected, state.default));
+            }
+        }
+        outcome
+    }
+}
+
+fn list<T: ToString>(items: &[T]) -> String {
+    if items.is_empty() {
+        return String::from("nothing");
+    }
+    items.iter().map(T::to_string).collect::<Vec<_>>().join(", ")
+}
+
+// The position of a key named in a step
+fn position(name: &str, keymaps: &KeyMapVec) -> Result<usize, String> {
+    if let Some(number) = name.strip_prefix('#') {
+        let index: usize = number.parse().map_err(|_| format!("Invalid position: {}", name))?;
+        return match keymaps.first() {
+            Some(keymap) if index < keymap.len() => Ok(index),
+            _ => Err(format!("No position {}", index))
+        };
+    }
+    let name = keycodes::canonical(name);
+    keymaps.iter()
+        .filter_map(|keymap| keymap.iter().position(|key| keycodes::canonical(&key.to_string()) == name))
+        .next()
+        .ok_or_else(|| format!("No key {} in the keymap", name))
+}
+
+// The events of one step starting at `time`, and the time it ends
+fn events(step: &str, keymaps: &KeyMapVec, time: u32, tapping_term: u32)
+          -> Result<(Vec<Event>, u32), String> {
+    let mut words = step.split_whitespace();
+    let verb = words.next().unwrap_or("");
+    let arg = words.next().ok_or_else(|| String::from("Missing key"))?;
+    if words.next().is_some() {
+        return Err(String::from("Too many words"));
+    }
+
+    let later = |ms: u32| time.checked_add(ms).ok_or_else(|| String::from("Time out of range"));
+    if verb == "wait" {
+        let ms: u32 = arg.parse().map_err(|_| format!("Invalid time: {}", arg))?;
+        return Ok((Vec::new(), later(ms)?));
+    }
+    if !["press", "release", "tap", "hold"].contains(&verb) {
+        return Err(format!("Unknown step: {}", verb));
+    }
+    let index = position(arg, keymaps)?;
+    Ok(match verb {
+        "press" => (vec![Event::press(time, index)], later(STEP)?),
+        "release" => (vec![Event::release(time, index)], later(STEP)?),
+        "tap" => (vec![Event::press(time, index), Event::release(later(STEP)?, index)], later(2 * STEP)?),
+        _ => (vec![Event::press(time, index)], later(tapping_term)?)
+    })
+}
+
+#[test]
+fn test_scenarios() {
+    let (keymaps, actions) = ::parser::parse_test_keymap("KEYMAP(J, FN4, A, 1), KEYMAP(DOWN, TRNS, TRNS, TRNS)",
+                                                         "[4] = ACTION_LAYER_TAP_KEY(1, SPC)");
+
+    let file = ScenarioFile::from_toml(r#"
+        keymap = "keymap.c"
+
+        [[scenario]]
+        name = "hold L_SPC, tap J emits DOWN"
+        steps = ["hold FN4", "tap J", "release FN4"]
+        keys = ["KC_DOWN"]
+        layers = []
+
+        [[scenario]]
+        name = "tap L_SPC"
+        steps = ["tap FN4", "tap #2"]
+        keys = ["SPC", "A", "B"]
+        layers = [1]
+
+        [[scenario]]
+        name = "bad step"
+        steps = ["jump J"]
+
+        [[scenario]]
+        name = "digits are keycodes"
+        steps = ["tap 1", "tap #9"]
+        keys = ["1"]
+
+        [[scenario]]
+        name = "too long"
+        steps = ["wait 4294967295", "tap J"]
+    "#).unwrap();
+    assert_eq!(file.keymap, "keymap.c");
+
+    let outcomes = file.run(&keymaps, &actions);
+    assert!(outcomes[0].passed(), "{:?}", outcomes[0]);
+    assert_eq!(outcomes[1].failures, vec![
+        "expected keys SPC, A, B, got SPC (layer 0, position 1), A (layer 0, position 2)",
+        "expected layers 1, got nothing"
+    ]);
+    assert_eq!(outcomes[2].failures, vec!["jump J: Unknown step: jump"]);
+    assert_eq!(outcomes[3].failures, vec!["tap #9: No position 9"]);
+    assert_eq!(outcomes[4].failures, vec!["tap J: Time out of range"]);
+
+    assert!(ScenarioFile::from_toml("keymap = \"k.c\"\n[[scenario]]\nname = \"x\"\nsteps = []\nkey = []").is_err());
+}
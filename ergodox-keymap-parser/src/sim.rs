@@ -0,0 +1,441 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use types::*;
+use keycodes;
+use resolve::*;
+
+/// TMK's default TAPPING_TERM, in milliseconds
+pub const TAPPING_TERM: u32 = 200;
+/// TMK's default TAPPING_TOGGLE: taps that lock a tap toggle layer on
+pub const TAPPING_TOGGLE: u32 = 5;
+
+/// A key going down or up at some time, in milliseconds
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Event {
+    pub time: u32,
+    pub index: usize,
+    pub pressed: bool
+}
+
+impl Event {
+    pub fn press(time: u32, index: usize) -> Event {
+        Event{ time, index, pressed: true }
+    }
+
+    pub fn release(time: u32, index: usize) -> Event {
+        Event{ time, index, pressed: false }
+    }
+}
+
+/// What the keyboard does in answer to events. Keycodes carry the layer
+/// and position they were pressed on.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Output {
+    KeyDown{ time: u32, code: String, layer: usize, index: usize },
+    KeyUp{ time: u32, code: String, layer: usize, index: usize },
+    Layers{ time: u32, state: LayerState }
+}
+
+// A key that is down, remembered so its release undoes what its press did
+// even if the layers have changed since
+#[derive(Debug, Clone, Copy)]
+struct Pressed {
+    layer: usize,
+    time: u32,
+    holding: bool
+}
+
+// Taps of a tap toggle key so far, and when the last one ended
+#[derive(Debug, Clone, Copy)]
+struct Taps {
+    count: u32,
+    time: u32
+}
+
+/// Runs a keymap the way TMK does. Tap keys wait until they are
+/// released or held past the tapping term, and keys pressed meanwhile
+/// wait with them.
+///
+/// `permissive_hold` is off by default, as PERMISSIVE_HOLD is in TMK's
+/// config.h. A key pressed and released inside a tap key that is then
+/// let go within the tapping term makes it a tap, so the other key is
+/// typed on the layer below. Turn it on to match firmware built with
+/// PERMISSIVE_HOLD.
+pub struct Simulator {
+    keymaps: KeyMapVec,
+    actions: ActionMap,
+    pub tapping_term: u32,
+    pub tapping_toggle: u32,
+    /// Hold a tap key as soon as another key is pressed and released
+    /// inside it, rather than waiting out the tapping term
+    pub permissive_hold: bool,
+    state: LayerState,
+    pressed: HashMap<usize, Pressed>,
+    pending: Option<usize>,
+    buffer: VecDeque<Event>,
+    taps: HashMap<u32, Taps>,
+    locked: HashSet<u32>,
+    outputs: Vec<Output>
+}
+
+impl Simulator {
+    pub fn new(keymaps: KeyMapVec, actions: ActionMap) -> Simulator {
+        Simulator {
+            keymaps,
+            actions,
+            tapping_term: TAPPING_TERM,
+            tapping_toggle: TAPPING_TOGGLE,
+            permissive_hold: false,
+            state: LayerState::new(0),
+            pressed: HashMap::new(),
+            pending: None,
+            buffer: VecDeque::new(),
+            taps: HashMap::new(),
+            locked: HashSet::new(),
+            outputs: Vec::new()
+        }
+    }
+
+    pub fn state(&self) -> LayerState {
+        self.state
+    }
+
+    pub fn outputs(&self) -> &[Output] {
+        &self.outputs
+    }
+
+    /// The outputs so far, leaving none behind
+    pub fn take_outputs(&mut self) -> Vec<Output> {
+        self.outputs.drain(..).collect()
+    }
+
+    /// Feed events in time order, then let any tap key still waiting
+    /// settle
+    pub fn run(&mut self, events: &[Event]) -> &[Output] {
+        for &event in events {
+            self.event(event);
+        }
+        if let Some(time) = events.last().map(|e| e.time) {
+            self.advance(time.saturating_add(self.tapping_term));
+        }
+        &self.outputs
+    }
+
+    pub fn event(&mut self, event: Event) {
+        self.advance(event.time);
+        match self.pending {
+            Some(index) if index == event.index && !event.pressed => {
+                self.pending = None;
+                self.release(event.time, index);
+                self.replay();
+            }
+            Some(_) => {
+                self.buffer.push_back(event);
+                if self.permissive_hold && !event.pressed
+                    && self.buffer.iter().any(|e| e.index == event.index && e.pressed) {
+                    self.hold(event.time);
+                }
+            }
+            None if event.pressed => self.press(event.time, event.index),
+            None => self.release(event.time, event.index)
+        }
+    }
+
+    /// Let the clock run to `time`, holding a tap key whose tapping term
+    /// has passed
+    pub fn advance(&mut self, time: u32) {
+        if let Some(index) = self.pending {
+            let term = self.pressed[&index].time.saturating_add(self.tapping_term);
+            if time >= term {
+                self.hold(term);
+            }
+        }
+    }
+
+    fn lookup(&self, layer: usize, index: usize) -> (Key, Option<Action>) {
+        let key = self.keymaps[layer][index].clone();
+        let action = match key {
+            Key::Fx(n) => self.actions.get(&n).cloned(),
+            Key::Key(_) => None
+        };
+        (key, action)
+    }
+
+    fn press(&mut self, time: u32, index: usize) {
+        let layer = match resolve(&self.keymaps, &self.actions, self.state, index) {
+            Some(resolved) => resolved.layer,
+            None => return
+        };
+        self.pressed.insert(index, Pressed{ layer, time, holding: false });
+
+        let action = match self.lookup(layer, index) {
+            (Key::Key(name), _) => return self.key_down(time, &name, layer, index),
+            (Key::Fx(_), None) => return,
+            (Key::Fx(_), Some(action)) => action
+        };
+        match action {
+            Action::LayerMomentary(n) => self.layers(time, |s| s.on(n)),
+            Action::LayerSetClear(n) => self.layers(time, |s| s.only(n)),
+            Action::LayerSet(n, ref when) if when != "ON_RELEASE" => self.layers(time, |s| s.only(n)),
+            Action::LayerTapToggle(n) if !self.locked.contains(&n) => self.layers(time, |s| s.on(n)),
+            Action::LayerTapKey(..) | Action::ModsTapKey(..) => self.pending = Some(index),
+            Action::ModsKey(m, k) => {
+                self.key_down(time, &m.to_string(), layer, index);
+                self.key_down(time, &k.to_string(), layer, index);
+            }
+            // Toggles and default layers change on release, and functions
+            // are up to the firmware
+            _ => ()
+        }
+    }
+
+    fn release(&mut self, time: u32, index: usize) {
+        let Pressed{ layer, time: since, holding } = match self.pressed.remove(&index) {
+            Some(pressed) => pressed,
+            None => return
+        };
+        let action = match self.lookup(layer, index) {
+            (Key::Key(name), _) => return self.key_up(time, &name, layer, index),
+            (Key::Fx(_), None) => return,
+            (Key::Fx(_), Some(action)) => action
+        };
+        match action {
+            Action::LayerMomentary(n) => self.layers(time, |s| s.off(n)),
+            Action::LayerSetClear(_) => self.layers(time, |s| s.layers = 0),
+            Action::LayerSet(n, ref when) if when != "ON_PRESS" => self.layers(time, |s| s.only(n)),
+            Action::LayerToggle(n) => self.layers(time, |s| s.toggle(n)),
+            Action::DefaultLayerSet(n) => self.layers(time, |s| s.default = n),
+            Action::LayerTapToggle(n) => self.tap_toggle(n, since, time),
+            Action::LayerTapKey(n, _) if holding => self.layers(time, |s| s.off(n)),
+            Action::ModsTapKey(m, _) if holding => self.key_up(time, &m.to_string(), layer, index),
+            Action::LayerTapKey(_, k) | Action::ModsTapKey(_, k) => {
+                let name = k.to_string();
+                self.key_down(time, &name, layer, index);
+                self.key_up(time, &name, layer, index);
+            }
+            Action::ModsKey(m, k) => {
+                self.key_up(time, &k.to_string(), layer, index);
+                self.key_up(time, &m.to_string(), layer, index);
+            }
+            _ => ()
+        }
+    }
+
+    // Settle the waiting tap key as held, then run the keys pressed
+    // while it waited
+    fn hold(&mut self, time: u32) {
+        let index = match self.pending.take() {
+            Some(index) => index,
+            None => return
+        };
+        let layer = match self.pressed.get_mut(&index) {
+            Some(pressed) => {
+                pressed.holding = true;
+                pressed.layer
+            }
+            None => return
+        };
+        match self.lookup(layer, index).1 {
+            Some(Action::LayerTapKey(n, _)) => self.layers(time, |s| s.on(n)),
+            Some(Action::ModsTapKey(m, _)) => self.key_down(time, &m.to_string(), layer, index),
+            _ => ()
+        }
+        self.replay();
+    }
+
+    fn replay(&mut self) {
+        let buffered: Vec<Event> = self.buffer.drain(..).collect();
+        for event in buffered {
+            self.event(event);
+        }
+    }
+
+    // The layer stays on while held; quick taps count towards locking it
+    // on, and any release after that turns it off again
+    fn tap_toggle(&mut self, layer: u32, since: u32, time: u32) {
+        if self.locked.remove(&layer) {
+            self.taps.remove(&layer);
+            return self.layers(time, |s| s.off(layer));
+        }
+        let term = self.tapping_term;
+        let count = if time.saturating_sub(since) >= term {
+            0
+        } else {
+            match self.taps.get(&layer) {
+                Some(taps) if since - taps.time.min(since) < term => taps.count + 1,
+                _ => 1
+            }
+        };
+        if count >= self.tapping_toggle {
+            self.taps.remove(&layer);
+            self.locked.insert(layer);
+        } else {
+            self.taps.insert(layer, Taps{ count, time });
+            self.layers(time, |s| s.off(layer));
+        }
+    }
+
+    fn layers<F: FnOnce(&mut LayerState)>(&mut self, time: u32, change: F) {
+        let before = self.state;
+        change(&mut self.state);
+        if self.state != before {
+            self.outputs.push(Output::Layers{ time, state: self.state });
+        }
+    }
+
+    fn key_down(&mut self, time: u32, name: &str, layer: usize, index: usize) {
+        if let Some(code) = keycode(name) {
+            self.outputs.push(Output::KeyDown{ time, code, layer, index });
+        }
+    }
+
+    fn key_up(&mut self, time: u32, name: &str, layer: usize, index: usize) {
+        if let Some(code) = keycode(name) {
+            self.outputs.push(Output::KeyUp{ time, code, layer, index });
+        }
+    }
+}
+
+// The HID keycode sent for a key, if it sends one
+fn keycode(name: &str) -> Option<String> {
+    let code = keycodes::canonical(name);
+    match code.as_str() {
+        "NO" | "TRNS" | "" => None,
+        _ => Some(code)
+    }
+}
+
+/// What the keys sent would type on a US host. Keys that type nothing
+/// show their legend in brackets, and backspace takes back a character.
+pub fn typed(outputs: &[Output]) -> String {
+    let mut text = String::new();
+    let mut shifts = 0;
+    for output in outputs {
+        let (code, down) = match output {
+            Output::KeyDown{ code, .. } => (code, true),
+            Output::KeyUp{ code, .. } => (code, false),
+            Output::Layers{ .. } => continue
+        };
+        match (code.as_str(), down) {
+            ("LSFT", true) | ("RSFT", true) => shifts += 1,
+            ("LSFT", false) | ("RSFT", false) => shifts -= 1,
+            (_, false) => (),
+            ("BSPC", _) => { text.pop(); }
+            ("ENT", _) | ("PENT", _) => text.push('\n'),
+            ("TAB", _) => text.push('\t'),
+            ("SPC", _) => text.push(' '),
+            (code, _) => {
+                let keycode = match keycodes::lookup(code) {
+                    Some(keycode) => keycode,
+                    None => {
+                        text.push_str(&format!("[{}]", code));
+                        continue
+                    }
+                };
+                let shifted = shifts > 0 && !keycode.shifted.is_empty();
+                match if shifted { keycode.shifted } else { keycode.normal } {
+                    c if c.chars().count() == 1 && c.chars().all(|c| c.is_ascii_alphabetic()) =>
+                        text.push_str(&if shifts > 0 { c.to_string() } else { c.to_lowercase() }),
+                    c if c.chars().count() == 1 && c.is_ascii() => text.push_str(c),
+                    "" => (),
+                    c => text.push_str(&format!("[{}]", c))
+                }
+            }
+        }
+    }
+    text
+}
+
+#[cfg(test)]
+fn test_simulator() -> Simulator {
+    let (keymaps, actions) = ::parser::parse_test_keymap("KEYMAP(KC_A, FN0, FN1, FN2, FN3, FN4),\n\
+                                                          KEYMAP(KC_1, KC_TRNS, KC_TRNS, KC_TRNS, KC_TRNS, KC_TRNS)",
+                                                         "[0] = ACTION_LAYER_TAP_KEY(1, KC_SPC),\n\
+                                                          [1] = ACTION_MODS_TAP_KEY(MOD_LSFT, KC_ENT),\n\
+                                                          [2] = ACTION_LAYER_TAP_TOGGLE(1),\n\
+                                                          [3] = ACTION_DEFAULT_LAYER_SET(1),\n\
+                                                          [4] = ACTION_LAYER_MOMENTARY(1)");
+    Simulator::new(keymaps, actions)
+}
+
+#[cfg(test)]
+fn codes(outputs: &[Output]) -> Vec<String> {
+    outputs.iter().map(|o| match o {
+        Output::KeyDown{ code, .. } => format!("+{}", code),
+        Output::KeyUp{ code, .. } => format!("-{}", code),
+        Output::Layers{ state, .. } => format!("L{:b}/{}", state.layers, state.default)
+    }).collect()
+}
+
+#[test]
+fn test_tap_keys() {
+    // A quick tap types the key, even with another key rolled over it
+    let mut sim = test_simulator();
+    sim.run(&[Event::press(0, 1), Event::press(50, 0), Event::release(80, 1), Event::release(90, 0)]);
+    assert_eq!(codes(sim.outputs()), vec!["+SPC", "-SPC", "+A", "-A"]);
+
+    // Held past the tapping term the layer comes on for the keys after it
+    let mut sim = test_simulator();
+    sim.run(&[Event::press(0, 1), Event::press(250, 0), Event::release(260, 0), Event::release(300, 1)]);
+    assert_eq!(codes(sim.outputs()), vec!["L10/0", "+1", "-1", "L0/0"]);
+    assert_eq!(sim.outputs()[0], Output::Layers{ time: 200, state: LayerState{ layers: 2, default: 0 } });
+    assert_eq!(sim.outputs()[1], Output::KeyDown{ time: 250, code: String::from("1"), layer: 1, index: 0 });
+
+    // Permissive hold settles it as soon as a key is tapped inside it
+    let events = [Event::press(0, 2), Event::press(20, 0), Event::release(40, 0), Event::release(60, 2)];
+    let mut sim = test_simulator();
+    sim.run(&events);
+    assert_eq!(codes(sim.outputs()), vec!["+ENT", "-ENT", "+A", "-A"]);
+    let mut sim = test_simulator();
+    sim.permissive_hold = true;
+    sim.run(&events);
+    assert_eq!(codes(sim.outputs()), vec!["+LSFT", "+A", "-A", "-LSFT"]);
+}
+
+#[test]
+fn test_layer_keys() {
+    // Releasing a key sends what it pressed, whatever layer is on now
+    let mut sim = test_simulator();
+    sim.run(&[Event::press(0, 5), Event::press(10, 0), Event::release(20, 5), Event::release(30, 0)]);
+    assert_eq!(codes(sim.outputs()), vec!["L10/0", "+1", "L0/0", "-1"]);
+
+    // Five taps lock a tap toggle layer on, and the next release frees it
+    let mut sim = test_simulator();
+    let taps: Vec<Event> = (0..5)
+        .flat_map(|n| vec![Event::press(n * 100, 3), Event::release(n * 100 + 50, 3)])
+        .collect();
+    sim.run(&taps);
+    assert!(sim.state().is_on(1));
+    sim.run(&[Event::press(1000, 3), Event::release(1050, 3)]);
+    assert!(!sim.state().is_on(1));
+
+    // Holding it is momentary and does not count as a tap
+    let mut sim = test_simulator();
+    sim.run(&[Event::press(0, 3), Event::release(500, 3)]);
+    assert_eq!(codes(sim.outputs()), vec!["L10/0", "L0/0"]);
+
+    // A release stamped before its press counts as a quick tap
+    let mut sim = test_simulator();
+    sim.run(&[Event::press(100, 3), Event::release(50, 3)]);
+    assert!(!sim.state().is_on(1));
+
+    // The default layer changes on release
+    let mut sim = test_simulator();
+    sim.event(Event::press(0, 4));
+    assert_eq!(sim.state().default, 0);
+    sim.event(Event::release(10, 4));
+    assert_eq!(sim.state(), LayerState::new(1));
+    sim.run(&[Event::press(20, 0), Event::release(30, 0)]);
+    assert_eq!(codes(&sim.take_outputs()), vec!["L0/1", "+1", "-1"]);
+}
+
+#[test]
+fn test_typed() {
+    let down = |code: &str| Output::KeyDown{ time: 0, code: String::from(code), layer: 0, index: 0 };
+    let up = |code: &str| Output::KeyUp{ time: 0, code: String::from(code), layer: 0, index: 0 };
+    let outputs = [
+        down("H"), up("H"), down("LSFT"), down("I"), down("1"), up("LSFT"), down("X"), down("BSPC"),
+        down("SPC"), down("ESC"), down("MY_KEY"), down("NO")
+    ];
+    assert_eq!(typed(&outputs), "hI! [Esc][MY_KEY]");
+}
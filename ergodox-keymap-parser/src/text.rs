@@ -0,0 +1,133 @@
+use render::*;
+use legend::Slot;
+
+/// Plain text listing of every key on every layer, one key per line
+pub struct TextRenderer {
+    output: String
+}
+
+impl TextRenderer {
+    pub fn new() -> TextRenderer {
+        TextRenderer{ output: String::new() }
+    }
+}
+
+impl Default for TextRenderer {
+    fn default() -> TextRenderer {
+        TextRenderer::new()
+    }
+}
+
+impl Renderer for TextRenderer {
+    type Output = String;
+
+    fn begin_layer(&mut self, layer: usize) {
+        if !self.output.is_empty() {
+            self.output.push('\n');
+        }
+        self.output.push_str(&format!("Layer {}\n", layer));
+    }
+
+    fn key(&mut self, key: &KeyView) {
+        // Shifted legends follow the legend they belong to
+        let mut legends: Vec<String> = Vec::new();
+        for legend in &key.legends {
+            match legends.last_mut() {
+                Some(last) if legend.slot == Slot::Shifted => {
+                    last.push(' ');
+                    last.push_str(&legend.text);
+                }
+                _ => legends.push(legend.text.clone())
+            }
+        }
+
+        let switch = match key.switch {
+            Some(LayerSwitch::On(layer)) => format!(" (layer {} on)", layer),
+            Some(LayerSwitch::Only(layer)) => format!(" (only layer {})", layer),
+            Some(LayerSwitch::Momentary(layer)) => format!(" (layer {} while held)", layer),
+            None => String::new()
+        };
+        let inherited = match key.inherited {
+            Some(layer) => format!(" (from layer {})", layer),
+            None => String::new()
+        };
+
+        self.output.push_str(&format!("{:>4}  {:<8} {}{}{}\n",
+                                      key.index, key.key.to_string(),
+                                      legends.join(" / "), switch, inherited));
+    }
+
+    fn finish(self) -> String {
+        self.output
+    }
+}
+
+#[test]
+fn test_text_output() {
+    use types::*;
+    use options::RenderOptions;
+
+    let mut actions = ActionMap::new();
+    actions.insert(1, Action::LayerTapKey(2, Key::Key(String::from("SPC"))));
+    let keymap = vec![Key::Key(String::from("KC_1")), Key::Fx(1), Key::Fx(3)];
+    let keyboard = Keyboard::with_options(vec![keymap], actions, RenderOptions::default());
+
+    assert_eq!(keyboard.render(TextRenderer::new()),
+               "Layer 0\n\
+                \x20  0  KC_1     1 !\n\
+                \x20  1  FN1      ␣ / ~L2 (layer 2 while held)\n\
+                \x20  2  FN3      BROKEN\n");
+}
+
+#[test]
+fn test_text_layers() {
+    use types::*;
+    use options::RenderOptions;
+
+    let keymaps = (0..3).map(|_| vec![Key::Key(String::from("KC_A"))]).collect();
+    let options = RenderOptions{ layers: vec![2], ..RenderOptions::default() };
+    let keyboard = Keyboard::with_options(keymaps, ActionMap::new(), options);
+
+    assert_eq!(keyboard.render(TextRenderer::new()), "Layer 2\n   0  KC_A     A\n");
+}
+
+#[test]
+fn test_text_host_layout() {
+    use options::RenderOptions;
+    use hostlayout::HostLayout;
+    use parser::parse_test_keymap;
+
+    let (keymaps, actions) = parse_test_keymap("KEYMAP(KC_LPRN, KC_PIPE, KC_EXLM, FN1, FN2)",
+                                               "[1] = ACTION_MODS_KEY(MOD_LSFT, KC_9),\n\
+                                                [2] = ACTION_MODS_KEY(MOD_LCTL, KC_9)");
+    let options = RenderOptions{ host_layout: HostLayout::named("de"), ..RenderOptions::default() };
+    let keyboard = Keyboard::with_options(keymaps, actions, options);
+
+    // On German, Shift+9 types ) and Shift+BSLS types '. Shift+1 is as
+    // on US.
+    assert_eq!(keyboard.render(TextRenderer::new()),
+               "Layer 0\n\
+                \x20  0  KC_LPRN  )\n\
+                \x20  1  KC_PIPE  '\n\
+                \x20  2  KC_EXLM  !\n\
+                \x20  3  FN1      )\n\
+                \x20  4  FN2      Ctrl+9\n");
+}
+
+#[test]
+fn test_text_transparent() {
+    use options::RenderOptions;
+    use parser::parse_test_keymap;
+
+    let (keymaps, actions) = parse_test_keymap("KEYMAP(KC_A, KC_TRNS), KEYMAP(KC_TRNS, KC_TRNS)", "");
+    let keyboard = Keyboard::with_options(keymaps, actions, RenderOptions::default());
+
+    assert_eq!(keyboard.render(TextRenderer::new()),
+               "Layer 0\n\
+                \x20  0  KC_A     A\n\
+                \x20  1  KC_TRNS  ▽\n\
+                \n\
+                Layer 1\n\
+                \x20  0  KC_TRNS  A / ▽ (from layer 0)\n\
+                \x20  1  KC_TRNS  ▽\n");
+}
@@ -0,0 +1,254 @@
+use std::collections::BTreeMap;
+use toml;
+
+use category::Category;
+
+/// Colours used to draw keys and legends
+#[derive(Debug, Clone, PartialEq)]
+pub struct Theme {
+    pub name: String,
+    // Fill behind the whole diagram, if any
+    pub background: Option<String>,
+    // Fill drawn under each key
+    pub key_background: String,
+    pub key_stroke: String,
+    // Top and bottom of the keycap sides
+    pub outside_top: String,
+    pub outside_bottom: String,
+    pub inside_stroke: String,
+    // Edges and middle of the keycap top
+    pub inside_edge: String,
+    pub inside_middle: String,
+    pub text: String,
+    pub shifted_text: String,
+    // Keycap top for each kind of key; the rest use the inside colours
+    pub categories: BTreeMap<Category, String>,
+    // Colour of each layer, repeating if there are more layers
+    pub layer_accents: Vec<String>
+}
+
+fn colours(colours: &[&str]) -> Vec<String> {
+    colours.iter().map(|c| String::from(*c)).collect()
+}
+
+fn fills(colours: &[(Category, &str)]) -> BTreeMap<Category, String> {
+    colours.iter().map(|&(c, fill)| (c, String::from(fill))).collect()
+}
+
+impl Theme {
+    /// The grey keycaps ergowhat has always drawn
+    pub fn light() -> Theme {
+        Theme{
+            name: String::from("light"),
+            background: None,
+            key_background: String::from("white"),
+            key_stroke: String::from("#A5A5A5"),
+            outside_top: String::from("#E1E1E1"),
+            outside_bottom: String::from("#B2B2B2"),
+            inside_stroke: String::from("#F9F9F9"),
+            inside_edge: String::from("#D6D6D6"),
+            inside_middle: String::from("#EBEBEB"),
+            text: String::from("black"),
+            shifted_text: String::from("black"),
+            categories: fills(&[
+                (Category::Number, "#E3ECF7"),
+                (Category::Symbol, "#EFE5F5"),
+                (Category::Modifier, "#FCE3C8"),
+                (Category::Layer, "#CDE8D5"),
+                (Category::Navigation, "#D3E6F5"),
+                (Category::Media, "#F6D5E5"),
+                (Category::Mouse, "#E9E2CC"),
+                (Category::Function, "#F5F0C5"),
+                (Category::Transparent, "#F7F7F7"),
+                (Category::Macro, "#F6D0CB")
+            ]),
+            layer_accents: colours(&["#A5A5A5", "#4C9BE8", "#E8A33D", "#5BB974",
+                                     "#D96B8A", "#9C7AE0", "#3DBFBF", "#C9B037"])
+        }
+    }
+
+    pub fn dark() -> Theme {
+        Theme{
+            name: String::from("dark"),
+            background: Some(String::from("#1E1E1E")),
+            key_background: String::from("#1E1E1E"),
+            key_stroke: String::from("#0A0A0A"),
+            outside_top: String::from("#4A4A4A"),
+            outside_bottom: String::from("#2B2B2B"),
+            inside_stroke: String::from("#5A5A5A"),
+            inside_edge: String::from("#3A3A3A"),
+            inside_middle: String::from("#474747"),
+            text: String::from("#E8E8E8"),
+            shifted_text: String::from("#A8A8A8"),
+            categories: fills(&[
+                (Category::Number, "#2F4159"),
+                (Category::Symbol, "#463654"),
+                (Category::Modifier, "#5A4024"),
+                (Category::Layer, "#2E5039"),
+                (Category::Navigation, "#2C4A63"),
+                (Category::Media, "#5A2F45"),
+                (Category::Mouse, "#4D4631"),
+                (Category::Function, "#56512A"),
+                (Category::Transparent, "#2A2A2A"),
+                (Category::Macro, "#5C302A")
+            ]),
+            layer_accents: colours(&["#0A0A0A", "#5AA9F0", "#F0B04F", "#6CCB85",
+                                     "#E9809D", "#B194F0", "#4FD3D3", "#DCC34A"])
+        }
+    }
+
+    pub fn high_contrast() -> Theme {
+        Theme{
+            name: String::from("high-contrast"),
+            background: Some(String::from("white")),
+            key_background: String::from("white"),
+            key_stroke: String::from("black"),
+            outside_top: String::from("white"),
+            outside_bottom: String::from("white"),
+            inside_stroke: String::from("black"),
+            inside_edge: String::from("white"),
+            inside_middle: String::from("white"),
+            text: String::from("black"),
+            shifted_text: String::from("black"),
+            // Only the keys that change what other keys do stand out
+            categories: fills(&[
+                (Category::Modifier, "#FFE500"),
+                (Category::Layer, "#7FDBFF")
+            ]),
+            layer_accents: colours(&["black", "#0050FF", "#D00000", "#008000",
+                                     "#A000A0", "#006060", "#804000", "#606060"])
+        }
+    }
+
+    /// Light keys with colours from the Okabe-Ito palette, which stay
+    /// distinct under the common forms of colour blindness
+    pub fn colour_blind() -> Theme {
+        Theme{
+            name: String::from("colour-blind"),
+            shifted_text: String::from("#0072B2"),
+            categories: fills(&[
+                (Category::Modifier, "#E69F00"),
+                (Category::Layer, "#56B4E9"),
+                (Category::Navigation, "#009E73"),
+                (Category::Function, "#F0E442"),
+                (Category::Media, "#CC79A7"),
+                (Category::Mouse, "#D55E00"),
+                (Category::Macro, "#0072B2")
+            ]),
+            layer_accents: colours(&["#000000", "#E69F00", "#56B4E9", "#009E73",
+                                     "#F0E442", "#0072B2", "#D55E00", "#CC79A7"]),
+            ..Theme::light()
+        }
+    }
+
+    /// One of the built in themes: light, dark, high-contrast or
+    /// colour-blind
+    pub fn named(name: &str) -> Option<Theme> {
+        match name {
+            "light" => Some(Theme::light()),
+            "dark" => Some(Theme::dark()),
+            "high-contrast" => Some(Theme::high_contrast()),
+            "colour-blind" | "color-blind" => Some(Theme::colour_blind()),
+            _ => None
+        }
+    }
+
+    /// Accent colour of a layer
+    pub fn accent(&self, layer: usize) -> Option<&str> {
+        if self.layer_accents.is_empty() {
+            None
+        } else {
+            Some(self.layer_accents[layer % self.layer_accents.len()].as_str())
+        }
+    }
+
+    /// Read a theme from TOML. Colours that are left out are taken from
+    /// the built in theme named by `base`, or from the light theme.
+    pub fn from_toml(input: &str) -> Result<Theme, String> {
+        let file: ThemeFile = toml::from_str(input).map_err(|e| e.to_string())?;
+
+        let base = match file.base {
+            Some(ref base) => Theme::named(base).ok_or_else(|| format!("Unknown theme: {}", base))?,
+            None => Theme::light()
+        };
+
+        let mut categories = base.categories;
+        for (name, fill) in file.categories.unwrap_or_default() {
+            let category = Category::from_name(&name)
+                .ok_or_else(|| format!("Unknown key category: {}", name))?;
+            categories.insert(category, fill);
+        }
+
+        Ok(Theme{
+            name: file.name.unwrap_or(base.name),
+            background: file.background.or(base.background),
+            key_background: file.key_background.unwrap_or(base.key_background),
+            key_stroke: file.key_stroke.unwrap_or(base.key_stroke),
+            outside_top: file.outside_top.unwrap_or(base.outside_top),
+            outside_bottom: file.outside_bottom.unwrap_or(base.outside_bottom),
+            inside_stroke: file.inside_stroke.unwrap_or(base.inside_stroke),
+            inside_edge: file.inside_edge.unwrap_or(base.inside_edge),
+            inside_middle: file.inside_middle.unwrap_or(base.inside_middle),
+            text: file.text.unwrap_or(base.text),
+            shifted_text: file.shifted_text.unwrap_or(base.shifted_text),
+            categories,
+            layer_accents: file.layer_accents.unwrap_or(base.layer_accents)
+        })
+    }
+}
+
+// A theme as written in TOML, where every colour is optional
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ThemeFile {
+    name: Option<String>,
+    base: Option<String>,
+    background: Option<String>,
+    key_background: Option<String>,
+    key_stroke: Option<String>,
+    outside_top: Option<String>,
+    outside_bottom: Option<String>,
+    inside_stroke: Option<String>,
+    inside_edge: Option<String>,
+    inside_middle: Option<String>,
+    text: Option<String>,
+    shifted_text: Option<String>,
+    // Category name to fill, e.g. `modifier = "#FCE3C8"`
+    categories: Option<BTreeMap<String, String>>,
+    layer_accents: Option<Vec<String>>
+}
+
+impl Default for Theme {
+    fn default() -> Theme {
+        Theme::light()
+    }
+}
+
+#[test]
+fn test_theme_toml() {
+    let theme = Theme::from_toml(r##"
+        name = "wiki"
+        base = "dark"
+        text = "#FFFFFF"
+
+        [categories]
+        layer = "#00FF00"
+    "##).unwrap();
+    let mut categories = Theme::dark().categories;
+    categories.insert(Category::Layer, String::from("#00FF00"));
+    assert_eq!(theme, Theme{
+        name: String::from("wiki"),
+        text: String::from("#FFFFFF"),
+        categories,
+        ..Theme::dark()
+    });
+
+    assert_eq!(Theme::from_toml("").unwrap(), Theme::light());
+    assert!(Theme::from_toml("base = \"neon\"").is_err());
+    assert!(Theme::from_toml("txet = \"red\"").is_err());
+    let accents = Theme::from_toml("layer_accents = [\"red\", \"blue\"]").unwrap();
+    assert_eq!(accents.accent(3), Some("blue"));
+    assert_eq!(Theme{ layer_accents: Vec::new(), ..Theme::light() }.accent(0), None);
+
+    assert!(Theme::from_toml("[categories]\nvowel = \"red\"").is_err());
+}
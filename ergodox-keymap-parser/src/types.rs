@@ -1,13 +1,13 @@
 use std::fmt;
 use std::collections::HashMap;
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Key {
     Fx(u32),
     Key(String)
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Action{
     // Run a specific function
     Function(Key),
@@ -52,3 +52,22 @@ impl fmt::Display for Key {
         Ok(())
     }
 }
+
+// Written as the TMK macro that defines the action
+impl fmt::Display for Action {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Action::Function(k) => write!(fmt, "ACTION_FUNCTION({})", k),
+            Action::FunctionTap(k) => write!(fmt, "ACTION_FUNCTION_TAP({})", k),
+            Action::DefaultLayerSet(l) => write!(fmt, "ACTION_DEFAULT_LAYER_SET({})", l),
+            Action::LayerSet(l, on) => write!(fmt, "ACTION_LAYER_SET({}, {})", l, on),
+            Action::LayerSetClear(l) => write!(fmt, "ACTION_LAYER_SET_CLEAR({})", l),
+            Action::LayerMomentary(l) => write!(fmt, "ACTION_LAYER_MOMENTARY({})", l),
+            Action::LayerTapKey(l, k) => write!(fmt, "ACTION_LAYER_TAP_KEY({}, {})", l, k),
+            Action::LayerTapToggle(l) => write!(fmt, "ACTION_LAYER_TAP_TOGGLE({})", l),
+            Action::LayerToggle(l) => write!(fmt, "ACTION_LAYER_TOGGLE({})", l),
+            Action::ModsKey(m, k) => write!(fmt, "ACTION_MODS_KEY({}, {})", m, k),
+            Action::ModsTapKey(m, k) => write!(fmt, "ACTION_MODS_TAP_KEY({}, {})", m, k)
+        }
+    }
+}
@@ -3,7 +3,15 @@ extern crate wasm_bindgen;
 extern crate ergodox_keymap_parser;
 
 use wasm_bindgen::prelude::*;
-use ergodox_keymap_parser::to_svg;
+use ergodox_keymap_parser::{to_svg, to_svg_with};
+use ergodox_keymap_parser::options::RenderOptions;
+use ergodox_keymap_parser::theme::Theme;
+use ergodox_keymap_parser::labels::LabelMap;
+use ergodox_keymap_parser::geometry::Geometry;
+use ergodox_keymap_parser::parser::parse_string;
+use ergodox_keymap_parser::sim::{typed, Event, Simulator};
+use ergodox_keymap_parser::keycodes;
+use ergodox_keymap_parser::graph::LayerGraph;
 
 #[wasm_bindgen]
 extern {
@@ -24,3 +32,106 @@ pub fn make_svg(contents: &str, id: &str){
     let elem = document.getElementById(id);
     elem.set_inner_html(&svg);
 }
+
+// Layers are given as a comma separated list, e.g. "0,2"; an empty
+// string draws them all. The other options are as ergowhat takes them,
+// given as text rather than files: a theme name or TOML theme, TOML
+// labels, a board name or KLE/QMK geometry JSON, and a key size such as
+// "19.05mm". Empty strings leave the defaults.
+#[wasm_bindgen]
+pub fn make_svg_with(contents: &str, id: &str, layers: &str, script: bool,
+                     theme: &str, labels: &str, geometry: &str, key_size: &str){
+    let mut options = RenderOptions{
+        layers: layers.split(',')
+            .map(str::trim)
+            .filter(|l| !l.is_empty())
+            .map(|l| l.parse().unwrap_or_else(|_| panic!("Invalid layer: {}", l)))
+            .collect(),
+        script,
+        ..RenderOptions::default()
+    };
+    if !theme.is_empty() {
+        options.theme = Theme::named(theme)
+            .unwrap_or_else(|| Theme::from_toml(theme).unwrap_or_else(|e| panic!("Invalid theme: {}", e)));
+    }
+    if !labels.is_empty() {
+        options.labels = LabelMap::from_toml(labels).unwrap_or_else(|e| panic!("Invalid labels: {}", e));
+    }
+    if !geometry.is_empty() {
+        options.geometry = Geometry::named(geometry)
+            .unwrap_or_else(|| Geometry::from_json(geometry, None).unwrap_or_else(|e| panic!("Invalid geometry: {}", e)));
+    }
+    if !key_size.is_empty() {
+        options.key_size = Some(key_size.parse().unwrap_or_else(|e| panic!("{}", e)));
+    }
+
+    let svg = to_svg_with(contents, &options);
+
+    let elem = document.getElementById(id);
+    elem.set_inner_html(&svg);
+}
+
+// Which layers lead to which, as a Mermaid flowchart to draw next to
+// the diagram
+#[wasm_bindgen]
+pub fn layer_graph(contents: &str) -> String {
+    let (keymaps, actions) = parse_string(contents);
+    LayerGraph::new(&keymaps, &actions).to_mermaid()
+}
+
+/// A keymap running in the page, pressed by clicking the diagram or
+/// typing on the computer's keyboard. Times are in milliseconds.
+#[wasm_bindgen]
+pub struct KeySimulator {
+    // Keycodes of the base layer, to find where a typed key is
+    base: Vec<String>,
+    sim: Simulator
+}
+
+#[wasm_bindgen]
+impl KeySimulator {
+    #[wasm_bindgen(constructor)]
+    pub fn new(contents: &str) -> KeySimulator {
+        let (keymaps, actions) = parse_string(contents);
+        let base = keymaps.first()
+            .map(|keymap| keymap.iter()
+                 .map(|key| keycodes::lookup(&key.to_string()).map_or(String::new(), |k| String::from(k.name())))
+                 .collect())
+            .unwrap_or_default();
+        KeySimulator{ base, sim: Simulator::new(keymaps, actions) }
+    }
+
+    pub fn press(&mut self, pos: usize, time: u32) {
+        self.sim.event(Event::press(time, pos));
+    }
+
+    pub fn release(&mut self, pos: usize, time: u32) {
+        self.sim.event(Event::release(time, pos));
+    }
+
+    pub fn tick(&mut self, time: u32) {
+        self.sim.advance(time);
+    }
+
+    pub fn tapping_term(&self) -> u32 {
+        self.sim.tapping_term
+    }
+
+    /// The position on the base layer of the key typed, given the `code`
+    /// of a DOM KeyboardEvent, or -1 if there is none
+    pub fn position(&self, code: &str) -> i32 {
+        keycodes::from_dom_code(code)
+            .and_then(|keycode| self.base.iter().position(|name| name == keycode.name()))
+            .map_or(-1, |pos| pos as i32)
+    }
+
+    /// The highest layer on, which is the one to show
+    pub fn layer(&self) -> u32 {
+        self.sim.state().highest()
+    }
+
+    /// What the keys pressed so far have typed
+    pub fn output(&self) -> String {
+        typed(self.sim.outputs())
+    }
+}
@@ -4,18 +4,31 @@ extern crate ergodox_keymap_parser;
 use std::ffi::{CString, CStr};
 use std::os::raw::c_char;
 
-use clap::{Arg, App};
+use clap::{Arg, App, AppSettings, ArgMatches, SubCommand};
 
 use ergodox_keymap_parser::*;
+use ergodox_keymap_parser::geometry::Geometry;
+use ergodox_keymap_parser::options::RenderOptions;
+use ergodox_keymap_parser::theme::Theme;
+use ergodox_keymap_parser::labels::LabelMap;
+use ergodox_keymap_parser::hostlayout::HostLayout;
+use ergodox_keymap_parser::parser::{parse_string, parse_with_source};
+use ergodox_keymap_parser::lint::{lint, report, Format};
+use ergodox_keymap_parser::graph::LayerGraph;
+use ergodox_keymap_parser::find::find;
+use ergodox_keymap_parser::coverage::Coverage;
+use ergodox_keymap_parser::scenario::ScenarioFile;
 use std::path::Path;
 use std::fs::File;
 use std::io::prelude::*;
+use std::process;
 
 fn main() {
     let matches = App::new("ergowhat")
         .version("0.1")
         .author("bnbeckwith <bnbeckwith@gmail.com>")
         .about("Prints out TMK/Ergodox layouts")
+        .setting(AppSettings::SubcommandsNegateReqs)
         .arg(Arg::with_name("FILE")
              .help("Keymap file to parse")
              .required(true)
@@ -26,20 +39,313 @@ fn main() {
              .value_name("OUTPUT")
              .help("Sets the output filename")
              .takes_value(true))
+        .arg(Arg::with_name("geometry")
+             .short("g")
+             .long("geometry")
+             .value_name("GEOMETRY")
+             .help("Reads key positions from keyboard-layout-editor JSON or a QMK info.json")
+             .takes_value(true))
+        .arg(Arg::with_name("board")
+             .short("b")
+             .long("board")
+             .value_name("BOARD")
+             .help("Selects a built in board geometry")
+             .possible_values(&["ergodox", "ergodox-staggered", "ergodox-flat"])
+             .conflicts_with("geometry"))
+        .arg(Arg::with_name("layout")
+             .short("l")
+             .long("layout")
+             .value_name("LAYOUT")
+             .help("Selects the layout to use from a QMK info.json")
+             .takes_value(true))
+        .arg(Arg::with_name("format")
+             .short("f")
+             .long("format")
+             .value_name("FORMAT")
+             .help("Sets the output format")
+             .possible_values(&["svg", "kle", "text", "html"])
+             .default_value("svg"))
+        .arg(Arg::with_name("layers")
+             .long("layers")
+             .value_name("LAYERS")
+             .help("Draws only these layers, e.g. 0,2,3")
+             .use_delimiter(true)
+             .takes_value(true))
+        .arg(Arg::with_name("no-script")
+             .long("no-script")
+             .help("Leaves out the layer switching script and draws every layer"))
+        .arg(Arg::with_name("key-size")
+             .long("key-size")
+             .value_name("SIZE")
+             .help("Sets the printed size of a 1u key, e.g. 19.05mm")
+             .takes_value(true))
+        .arg(Arg::with_name("theme")
+             .short("t")
+             .long("theme")
+             .value_name("THEME")
+             .help("Sets the colours: light, dark, high-contrast, colour-blind or a TOML theme file")
+             .takes_value(true))
+        .arg(Arg::with_name("label")
+             .long("label")
+             .value_name("KEY=TEXT")
+             .help("Shows TEXT on every KEY")
+             .multiple(true)
+             .number_of_values(1)
+             .takes_value(true))
+        .arg(Arg::with_name("os")
+             .long("os")
+             .value_name("OS")
+             .help("Names the modifiers as this operating system does")
+             .possible_values(&["mac", "win", "linux"])
+             .takes_value(true))
+        .arg(Arg::with_name("host-layout")
+             .long("host-layout")
+             .value_name("LAYOUT")
+             .help("Shows what keys type when the computer is set to this layout")
+             .possible_values(&["us", "uk", "de", "fr", "dvorak", "colemak"])
+             .takes_value(true))
+        .arg(Arg::with_name("labels")
+             .long("labels")
+             .value_name("LABELS")
+             .help("Reads legends for keycodes, actions and positions from a TOML file")
+             .takes_value(true))
+        .arg(Arg::with_name("mermaid-script")
+             .long("mermaid-script")
+             .value_name("URL")
+             .help("Loads Mermaid from URL to draw the layer graph in HTML output, which is otherwise left as text")
+             .takes_value(true))
+        .subcommand(SubCommand::with_name("test")
+                    .about("Runs the scenarios in a TOML file against the keymap it names")
+                    .arg(Arg::with_name("SCENARIOS")
+                         .help("Scenario file to run")
+                         .required(true)
+                         .index(1)))
+        .subcommand(SubCommand::with_name("lint")
+                    .about("Checks a keymap for mistakes, exiting with 1 if there are any")
+                    .arg(Arg::with_name("FILE")
+                         .help("Keymap file to check")
+                         .required(true)
+                         .index(1))
+                    .arg(Arg::with_name("geometry")
+                         .short("g")
+                         .long("geometry")
+                         .value_name("GEOMETRY")
+                         .help("Reads key positions from keyboard-layout-editor JSON or a QMK info.json")
+                         .takes_value(true))
+                    .arg(Arg::with_name("board")
+                         .short("b")
+                         .long("board")
+                         .value_name("BOARD")
+                         .help("Selects a built in board geometry")
+                         .possible_values(&["ergodox", "ergodox-staggered", "ergodox-flat"])
+                         .conflicts_with("geometry"))
+                    .arg(Arg::with_name("layout")
+                         .short("l")
+                         .long("layout")
+                         .value_name("LAYOUT")
+                         .help("Selects the layout to use from a QMK info.json")
+                         .takes_value(true))
+                    .arg(Arg::with_name("format")
+                         .short("f")
+                         .long("format")
+                         .value_name("FORMAT")
+                         .help("Sets the output format")
+                         .possible_values(&["text", "json", "sarif", "github"])
+                         .default_value("text")))
+        .subcommand(SubCommand::with_name("graph")
+                    .about("Prints which layers lead to which, warning of layers that can't be reached or left")
+                    .arg(Arg::with_name("FILE")
+                         .help("Keymap file to parse")
+                         .required(true)
+                         .index(1))
+                    .arg(Arg::with_name("format")
+                         .short("f")
+                         .long("format")
+                         .value_name("FORMAT")
+                         .help("Sets the output format")
+                         .possible_values(&["dot", "mermaid"])
+                         .default_value("dot")))
+        .subcommand(SubCommand::with_name("find")
+                    .about("Lists every key that types a keycode, and how to get to it from the base layer")
+                    .arg(Arg::with_name("KEYCODE")
+                         .help("Keycode to look for, e.g. KC_PIPE")
+                         .required(true)
+                         .index(1))
+                    .arg(Arg::with_name("FILE")
+                         .help("Keymap file to parse")
+                         .required(true)
+                         .index(2)))
+        .subcommand(SubCommand::with_name("coverage")
+                    .about("Lists standard keycodes that no key can send, and those on more than one layer")
+                    .arg(Arg::with_name("FILE")
+                         .help("Keymap file to parse")
+                         .required(true)
+                         .index(1)))
         .get_matches();
 
+    if let Some(matches) = matches.subcommand_matches("coverage") {
+        let keymap_file = matches.value_of("FILE").unwrap();
+        let input = read_file(keymap_file, "keymap");
+        let (keymaps, actions) = parse_string(&input);
+
+        print!("{}", Coverage::new(&keymaps, &actions));
+        return;
+    }
+    if let Some(matches) = matches.subcommand_matches("find") {
+        let keycode = matches.value_of("KEYCODE").unwrap();
+        let keymap_file = matches.value_of("FILE").unwrap();
+        let input = read_file(keymap_file, "keymap");
+        let (keymaps, actions) = parse_string(&input);
+
+        let found = find(&keymaps, &actions, keycode);
+        if found.is_empty() {
+            println!("No key types {}", keycode);
+            process::exit(1);
+        }
+        for found in found {
+            println!("{}", found);
+        }
+        return;
+    }
+    if let Some(matches) = matches.subcommand_matches("graph") {
+        let keymap_file = matches.value_of("FILE").unwrap();
+        let input = read_file(keymap_file, "keymap");
+        let (keymaps, actions) = parse_string(&input);
+
+        let graph = LayerGraph::new(&keymaps, &actions);
+        for problem in graph.problems() {
+            eprintln!("warning: {}", problem);
+        }
+        match matches.value_of("format").unwrap() {
+            "mermaid" => print!("{}", graph.to_mermaid()),
+            _ => print!("{}", graph.to_dot())
+        }
+        return;
+    }
+    if let Some(matches) = matches.subcommand_matches("test") {
+        let passed = test(matches.value_of("SCENARIOS").unwrap());
+        process::exit(if passed { 0 } else { 1 });
+    }
+    if let Some(matches) = matches.subcommand_matches("lint") {
+        let format = matches.value_of("format").unwrap().parse().unwrap_or_else(|e| panic!("{}", e));
+        let clean = check(matches.value_of("FILE").unwrap(), &geometry(matches), format);
+        process::exit(if clean { 0 } else { 1 });
+    }
+
     let keymap_file = matches.value_of("FILE").unwrap();
-    let output_file = matches.value_of("OUTPUT").unwrap_or("keymap.svg");
+    let format = matches.value_of("format").unwrap();
+    let output_file = matches.value_of("output").unwrap_or(match format {
+        "kle" => "keymap.json",
+        "text" => "keymap.txt",
+        "html" => "keymap.html",
+        _ => "keymap.svg"
+    });
 
-    let mut f = File::open(Path::new(keymap_file)).expect("File couldn't be opened");
-    let mut input = String::new();
-    f.read_to_string(&mut input).expect("Unable to read file");
+    let input = read_file(keymap_file, "keymap");
     
-    let svg = to_svg(&input);
+    let mut options = RenderOptions{
+        geometry: geometry(&matches),
+        script: !matches.is_present("no-script"),
+        ..RenderOptions::default()
+    };
+    if let Some(layers) = matches.values_of("layers") {
+        options.layers = layers
+            .map(|l| l.parse().unwrap_or_else(|_| panic!("Invalid layer: {}", l)))
+            .collect();
+    }
+    if let Some(theme) = matches.value_of("theme") {
+        options.theme = match Theme::named(theme) {
+            Some(theme) => theme,
+            None => {
+                Theme::from_toml(&read_file(theme, "theme")).unwrap_or_else(|e| panic!("Invalid theme: {}", e))
+            }
+        };
+    }
+    if let Some(size) = matches.value_of("key-size") {
+        options.key_size = Some(size.parse().unwrap_or_else(|e| panic!("{}", e)));
+    }
+    if let Some(os) = matches.value_of("os") {
+        options.os = Some(os.parse().unwrap_or_else(|e| panic!("{}", e)));
+    }
+    if let Some(layout) = matches.value_of("host-layout") {
+        options.host_layout = HostLayout::named(layout);
+    }
+    options.mermaid_script = matches.value_of("mermaid-script").map(String::from);
+    if let Some(labels_file) = matches.value_of("labels") {
+        options.labels = LabelMap::from_toml(&read_file(labels_file, "labels")).unwrap_or_else(|e| panic!("Invalid labels: {}", e));
+    }
+    if let Some(labels) = matches.values_of("label") {
+        for label in labels {
+            let mut parts = label.splitn(2, '=');
+            let key = parts.next().unwrap();
+            let text = parts.next().unwrap_or_else(|| panic!("Invalid label: {}", label));
+            options.labels.insert_key(key, text);
+        }
+    }
+
+    let rendered = match format {
+        "kle" => render(&input, &options, KleRenderer::new()),
+        "text" => render(&input, &options, TextRenderer::new()),
+        "html" => to_html_with(&input, &options),
+        _ => render(&input, &options, SvgRenderer::new())
+    };
     let mut output = File::create(Path::new(output_file)).unwrap();
-    output.write_all(&svg.into_bytes()).expect("Couldn't write file");
+    output.write_all(&rendered.into_bytes()).expect("Couldn't write file");
 }
 
+// The contents of a file, or a panic naming `what` it was meant to be
+fn read_file<P: AsRef<Path>>(path: P, what: &str) -> String {
+    let mut f = File::open(path).unwrap_or_else(|e| panic!("Couldn't open {} file: {}", what, e));
+    let mut contents = String::new();
+    f.read_to_string(&mut contents).unwrap_or_else(|e| panic!("Unable to read {} file: {}", what, e));
+    contents
+}
+
+// The board chosen with --geometry and --layout, or --board
+fn geometry(matches: &ArgMatches) -> Geometry {
+    match matches.value_of("geometry") {
+        Some(geometry_file) => {
+            Geometry::from_json(&read_file(geometry_file, "geometry"), matches.value_of("layout"))
+                .unwrap_or_else(|e| panic!("Invalid geometry: {}", e))
+        }
+        None => Geometry::named(matches.value_of("board").unwrap_or("ergodox")).unwrap()
+    }
+}
+
+// Lint a keymap, printing the findings, and whether there were none
+fn check(keymap_file: &str, geometry: &Geometry, format: Format) -> bool {
+    let input = read_file(keymap_file, "keymap");
+    let (keymaps, actions, source) = parse_with_source(&input);
+
+    let findings = lint(&keymaps, &actions, &source, geometry.keys.len());
+    print!("{}", report(&findings, keymap_file, &input, format));
+    findings.is_empty()
+}
+
+// Run a scenario file, reporting each scenario, and whether they all passed
+fn test(scenario_file: &str) -> bool {
+    let path = Path::new(scenario_file);
+    let scenarios = ScenarioFile::from_toml(&read_file(path, "scenario")).unwrap_or_else(|e| panic!("Invalid scenarios: {}", e));
+
+    let keymap_path = path.parent().unwrap_or_else(|| Path::new("")).join(&scenarios.keymap);
+    let input = read_file(&keymap_path, "keymap");
+    let (keymaps, actions) = parse_string(&input);
+
+    let outcomes = scenarios.run(&keymaps, &actions);
+    for outcome in &outcomes {
+        if outcome.passed() {
+            println!("ok     {}", outcome.name);
+        } else {
+            println!("FAILED {}", outcome.name);
+            for failure in &outcome.failures {
+                println!("       {}", failure);
+            }
+        }
+    }
+    let failed = outcomes.iter().filter(|o| !o.passed()).count();
+    println!("\n{} passed, {} failed", outcomes.len() - failed, failed);
+    failed == 0
+}
 
 #[no_mangle]
 pub extern "C" fn svg(data: *mut c_char) -> *mut c_char {